@@ -0,0 +1,113 @@
+//! Just enough of the solidity ABI for revert reasons and precompiles.
+
+use revm::primitives::{Address, U256, keccak256};
+
+/// The 4 byte selector of a function, event or error with `signature`, such as
+/// `transfer(address,uint256)`.
+#[must_use]
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Decodes ABI encoded arguments, checking the bounds of every offset and length.
+pub struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    #[must_use]
+    pub fn new(args: &'a [u8]) -> Self {
+        Self(args)
+    }
+
+    fn word_at(&self, offset: usize) -> Option<&'a [u8]> {
+        self.0.get(offset..offset.checked_add(32)?)
+    }
+
+    fn usize_at(&self, offset: usize) -> Option<usize> {
+        U256::from_be_slice(self.word_at(offset)?).try_into().ok()
+    }
+
+    /// The `uint256` argument at `index`.
+    #[must_use]
+    pub fn uint(&self, index: usize) -> Option<U256> {
+        self.word_at(index.checked_mul(32)?)
+            .map(U256::from_be_slice)
+    }
+
+    /// The `address` argument at `index`.
+    #[must_use]
+    pub fn address(&self, index: usize) -> Option<Address> {
+        let word = self.word_at(index.checked_mul(32)?)?;
+
+        word[..12]
+            .iter()
+            .all(|byte| *byte == 0)
+            .then(|| Address::from_slice(&word[12..]))
+    }
+
+    /// The `bool` argument at `index`.
+    #[must_use]
+    pub fn bool(&self, index: usize) -> Option<bool> {
+        match self.uint(index)?.try_into() {
+            Ok(0_u8) => Some(false),
+            Ok(1) => Some(true),
+            _ => None,
+        }
+    }
+
+    /// The `bytes` argument at `index`.
+    #[must_use]
+    pub fn bytes(&self, index: usize) -> Option<&'a [u8]> {
+        let offset = self.usize_at(index.checked_mul(32)?)?;
+        let len = self.usize_at(offset)?;
+        let start = offset.checked_add(32)?;
+
+        self.0.get(start..start.checked_add(len)?)
+    }
+
+    /// The `string` argument at `index`.
+    #[must_use]
+    pub fn string(&self, index: usize) -> Option<String> {
+        String::from_utf8(self.bytes(index)?.to_vec()).ok()
+    }
+}
+
+/// A value to ABI encode.
+pub enum Token {
+    Uint(U256),
+    Address(Address),
+    Bool(bool),
+    Bytes(Vec<u8>),
+    String(String),
+}
+
+/// ABI encodes `tokens` as the arguments of a call, or the return values of a function.
+#[must_use]
+pub fn encode(tokens: &[Token]) -> Vec<u8> {
+    let mut head = Vec::with_capacity(tokens.len() * 32);
+    let mut tail = vec![];
+
+    for token in tokens {
+        match token {
+            Token::Uint(value) => head.extend(value.to_be_bytes::<32>()),
+            Token::Address(address) => head.extend(address.into_word()),
+            Token::Bool(value) => head.extend(U256::from(u8::from(*value)).to_be_bytes::<32>()),
+            Token::Bytes(bytes) => encode_bytes(bytes, tokens.len(), &mut head, &mut tail),
+            Token::String(string) => {
+                encode_bytes(string.as_bytes(), tokens.len(), &mut head, &mut tail);
+            }
+        }
+    }
+
+    head.extend(tail);
+    head
+}
+
+/// Appends the offset of `bytes` to `head` and `bytes` itself, padded to a multiple of 32 bytes, to
+/// `tail`.
+fn encode_bytes(bytes: &[u8], tokens: usize, head: &mut Vec<u8>, tail: &mut Vec<u8>) {
+    head.extend(U256::from(tokens * 32 + tail.len()).to_be_bytes::<32>());
+    tail.extend(U256::from(bytes.len()).to_be_bytes::<32>());
+    tail.extend(bytes);
+    tail.resize(tail.len().next_multiple_of(32), 0);
+}
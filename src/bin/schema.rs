@@ -1,11 +1,12 @@
-use cosmwasm_evm::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg};
+use cosmwasm_evm::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg};
 use cosmwasm_schema::write_api;
 
 fn main() {
     write_api! {
         instantiate: InstantiateMsg,
         execute: ExecuteMsg,
-        // query: QueryMsg,
+        query: QueryMsg,
         migrate: MigrateMsg,
+        sudo: SudoMsg,
     }
 }
@@ -1,155 +1,951 @@
 use cosmwasm_std::{
-    Coin, CosmosMsg, DepsMut, Env, Event, MessageInfo, OverflowError, OverflowOperation, Response,
-    StdError, StdResult, SubMsg, Uint128, Uint256, entry_point,
+    Api, BankMsg, Binary, BlockInfo, CanonicalAddr, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env,
+    Event, HexBinary, MessageInfo, Reply, Response, StdError, Storage, SubMsg, Uint128, Uint256,
+    WasmMsg, entry_point, to_json_binary,
+};
+use revm::{
+    Database,
+    interpreter::gas::CALL_STIPEND,
+    primitives::{
+        Address, B256, ExecutionResult, HaltReason, KECCAK_EMPTY, Output, SuccessReason, TxEnv,
+        U256, keccak256,
+    },
 };
-use revm::primitives::{Address, ExecutionResult, Output, SuccessReason, U256};
 use sha2::Digest;
 
 use crate::{
-    evm::Evm,
-    msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, Tx, TxKind},
-    state::{AccountInfoStore, Config, ConfigStore, RawAccountInfo, StorageExt},
+    error::ContractError,
+    evm::{Evm, block_hash},
+    fee,
+    logs::{DEFAULT_LOGS_LIMIT, MAX_LOGS_LIMIT, filter_logs, store_logs},
+    msg::{
+        AccountInfoResponse, Addr, CallResponse, DEFAULT_GAS_RATIO, DEFAULT_SDK_GAS_LIMIT,
+        EstimateGasResponse, ExecuteMsg, FeeHistoryResponse, H256, InstantiateMsg, Log, LogCursor,
+        LogFilter, MigrateMsg, ProofResponse, QueryMsg, RandomnessSource, StorageProof, SudoMsg,
+        TransactionReceipt, Tx, TxKind,
+    },
+    precompiles::{self, BANK_ADDRESS, Message, WASM_ADDRESS},
+    revert::RevertReason,
+    state,
+    state::{
+        AccountInfoStore, AccountStorageStore, AddressMappingStore, BlockHashStore,
+        BlockNumberStore, BlockStore, Config, ConfigStore, ContractsStore, FEE_HISTORY,
+        LegacyAccountStore, LegacyConfigStore, RawAccountInfo, RawBlockHash, ReceiptStore,
+        StateRootStore, StorageExt, StorageRootStore, WasmReplyStore,
+    },
+    trie,
+    tx::{SignedTx, TxLegacy, TypedTx, unsigned_tx_hash},
 };
 
 #[entry_point]
 #[allow(clippy::needless_pass_by_value)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response> {
-    deps.storage.write::<ConfigStore>(
-        (),
-        &Config {
-            denom: msg.eth_token,
-        },
-    );
+) -> Result<Response, ContractError> {
+    let state_trie = msg.state_trie.unwrap_or_default();
+
+    deps.storage
+        .write::<ConfigStore>((), &new_config(msg, env.block.height));
+
+    if state_trie {
+        deps.storage.write::<StateRootStore>((), &trie::EMPTY_ROOT);
+    }
 
     Ok(Response::default())
 }
 
-// #[entry_point]
-// #[allow(clippy::needless_pass_by_value)]
-// pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-//     match msg {
-//         QueryMsg::LinkedAddress { github_user_id } => Ok(to_json_binary(
-//             &USERS.may_load(deps.storage, github_user_id)?,
-//         )?),
-//     }
-// }
+/// The config of a deployment instantiated with `msg` at `height`.
+fn new_config(msg: InstantiateMsg, height: u64) -> Config {
+    let coinbase = msg.coinbase.map(|coinbase| coinbase.0).unwrap_or_default();
+
+    Config {
+        denom: msg.eth_token,
+        chain_id: msg.chain_id,
+        coinbase,
+        prevrandao: msg.prevrandao.unwrap_or(RandomnessSource::BlockHash),
+        gas_ratio: msg.gas_ratio.unwrap_or(DEFAULT_GAS_RATIO),
+        sdk_gas_limit: msg.sdk_gas_limit.unwrap_or(DEFAULT_SDK_GAS_LIMIT),
+        fee_collector: msg
+            .fee_collector
+            .map_or(coinbase, |fee_collector| fee_collector.0),
+        base_fee: msg
+            .base_fee
+            .map(|base_fee| U256::from_be_bytes(base_fee.to_be_bytes()))
+            .unwrap_or_default(),
+        base_fee_height: height,
+    }
+}
+
+#[entry_point]
+#[allow(clippy::needless_pass_by_value)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+    let res = match msg {
+        QueryMsg::AccountInfo { address } => {
+            let account = deps
+                .storage
+                .read::<AccountInfoStore>(address.0)?
+                .unwrap_or_default();
+
+            to_json_binary(&AccountInfoResponse {
+                balance: Uint256::from_be_bytes(account.balance),
+                nonce: account.nonce,
+                code_hash: H256(account.code_hash.into()),
+            })
+        }
+        QueryMsg::Balance { address } => to_json_binary(&Uint256::from_be_bytes(
+            deps.storage
+                .read::<AccountInfoStore>(address.0)?
+                .unwrap_or_default()
+                .balance,
+        )),
+        QueryMsg::Nonce { address } => to_json_binary(
+            &deps
+                .storage
+                .read::<AccountInfoStore>(address.0)?
+                .unwrap_or_default()
+                .nonce,
+        ),
+        QueryMsg::Code { address } => {
+            let account = deps
+                .storage
+                .read::<AccountInfoStore>(address.0)?
+                .unwrap_or_default();
+
+            to_json_binary(&code_by_hash(deps, account.code_hash.into())?)
+        }
+        QueryMsg::CodeByHash { hash } => to_json_binary(&code_by_hash(deps, hash.0)?),
+        QueryMsg::Call { tx, from } => to_json_binary(&call(
+            &mut Evm::new(deps.storage, deps.api, deps.querier, &env)?,
+            from.map(|from| from.0).unwrap_or_default(),
+            tx,
+        )?),
+        QueryMsg::EstimateGas { tx, from } => to_json_binary(&estimate_gas(
+            &mut Evm::new(deps.storage, deps.api, deps.querier, &env)?,
+            from.map(|from| from.0).unwrap_or_default(),
+            tx,
+        )?),
+        QueryMsg::TransactionReceipt { hash } => {
+            to_json_binary(&deps.storage.read::<ReceiptStore>(hash.0)?)
+        }
+        QueryMsg::BlockByNumber { number } => {
+            to_json_binary(&deps.storage.read::<BlockStore>(number)?)
+        }
+        QueryMsg::BlockByHash { hash } => {
+            to_json_binary(&match deps.storage.read::<BlockNumberStore>(hash.0)? {
+                Some(number) => deps.storage.read::<BlockStore>(number)?,
+                None => None,
+            })
+        }
+        QueryMsg::StateRoot {} => {
+            to_json_binary(&deps.storage.read::<StateRootStore>(())?.map(H256))
+        }
+        QueryMsg::Proof {
+            address,
+            storage_keys,
+        } => to_json_binary(&proof(deps, address.0, &storage_keys)?),
+        QueryMsg::BaseFee {} => {
+            let config = deps
+                .storage
+                .read::<ConfigStore>(())?
+                .ok_or(ContractError::MissingConfig)?;
+
+            to_json_binary(&Uint256::from_be_bytes(
+                fee::base_fee(deps.storage, &config, env.block.height)?.to_be_bytes(),
+            ))
+        }
+        QueryMsg::FeeHistory {
+            block_count,
+            newest_block,
+        } => to_json_binary(&fee_history(deps, &env, block_count, newest_block)?),
+        QueryMsg::Logs {
+            filter,
+            start_after,
+            limit,
+        } => to_json_binary(&logs(deps, &env, &filter, start_after, limit)?),
+        QueryMsg::EvmAddress { address } => {
+            to_json_binary(&Addr(evm_address(&deps.api.addr_canonicalize(&address)?)))
+        }
+        QueryMsg::CosmosAddress { address } => to_json_binary(
+            &deps
+                .storage
+                .read::<AddressMappingStore>(address.0)?
+                .map(|canonical| deps.api.addr_humanize(&canonical))
+                .transpose()?,
+        ),
+        QueryMsg::Storage { address, slot } => to_json_binary(&H256(
+            deps.storage
+                .read::<AccountStorageStore>((address.0, slot.0.into()))?
+                .unwrap_or_default()
+                .into(),
+        )),
+    }?;
+
+    Ok(res)
+}
+
+fn code_by_hash(deps: Deps, code_hash: B256) -> Result<HexBinary, ContractError> {
+    Ok(deps
+        .storage
+        .read::<ContractsStore>(code_hash)?
+        .map(|code| code.original_bytes().to_vec().into())
+        .unwrap_or_default())
+}
+
+/// The base fee and gas usage of up to `block_count` blocks ending at `newest_block`, in the manner
+/// of `eth_feeHistory`.
+fn fee_history(
+    deps: Deps,
+    env: &Env,
+    block_count: u64,
+    newest_block: Option<u64>,
+) -> Result<FeeHistoryResponse, ContractError> {
+    let config = deps
+        .storage
+        .read::<ConfigStore>(())?
+        .ok_or(ContractError::MissingConfig)?;
+
+    let newest = newest_block.map_or(env.block.height, |newest| newest.min(env.block.height));
+    let oldest = newest
+        .saturating_add(1)
+        .saturating_sub(block_count.min(FEE_HISTORY));
+
+    let (base_fees, gas_used) = fee::fee_history(deps.storage, &config, oldest, newest)?;
+
+    Ok(FeeHistoryResponse {
+        oldest_block: oldest,
+        base_fee_per_gas: base_fees
+            .into_iter()
+            .map(|base_fee| Uint256::from_be_bytes(base_fee.to_be_bytes()))
+            .collect(),
+        gas_used_ratio: gas_used
+            .into_iter()
+            .map(|gas_used| {
+                Decimal::checked_from_ratio(gas_used.min(config.gas_limit()), config.gas_limit())
+                    .unwrap_or_default()
+            })
+            .collect(),
+    })
+}
+
+/// The account at `address` and the values of `storage_keys`, with their proofs, in the manner of
+/// `eth_getProof`.
+fn proof(
+    deps: Deps,
+    address: Address,
+    storage_keys: &[H256],
+) -> Result<ProofResponse, ContractError> {
+    let state_root = deps
+        .storage
+        .read::<StateRootStore>(())?
+        .ok_or(ContractError::StateTrieDisabled)?;
+
+    let account = deps
+        .storage
+        .read::<AccountInfoStore>(address)?
+        .unwrap_or_default();
+    let storage_root = deps
+        .storage
+        .read::<StorageRootStore>(address)?
+        .unwrap_or(trie::EMPTY_ROOT);
+
+    let to_hex = |proof: Vec<Vec<u8>>| proof.into_iter().map(HexBinary::from).collect();
+
+    Ok(ProofResponse {
+        address: Addr(address),
+        balance: Uint256::from_be_bytes(account.balance),
+        nonce: account.nonce,
+        code_hash: H256(account.code_hash.into()),
+        storage_hash: H256(storage_root),
+        account_proof: to_hex(trie::proof(
+            deps.storage,
+            state_root,
+            keccak256(address).as_slice(),
+        )?),
+        storage_proof: storage_keys
+            .iter()
+            .map(|key| {
+                Ok(StorageProof {
+                    key: *key,
+                    value: H256(
+                        deps.storage
+                            .read::<AccountStorageStore>((address, key.0.into()))?
+                            .unwrap_or_default()
+                            .into(),
+                    ),
+                    proof: to_hex(trie::proof(
+                        deps.storage,
+                        storage_root,
+                        keccak256(key.0).as_slice(),
+                    )?),
+                })
+            })
+            .collect::<Result<_, ContractError>>()?,
+    })
+}
+
+/// The logs matching `filter`, in the manner of `eth_getLogs`.
+fn logs(
+    deps: Deps,
+    env: &Env,
+    filter: &LogFilter,
+    start_after: Option<LogCursor>,
+    limit: Option<u32>,
+) -> Result<Vec<Log>, ContractError> {
+    let from = filter.from_block.unwrap_or(env.block.height);
+    let to = filter
+        .to_block
+        .map_or(env.block.height, |to| to.min(env.block.height));
+    let limit = limit.unwrap_or(DEFAULT_LOGS_LIMIT).min(MAX_LOGS_LIMIT);
+
+    Ok(filter_logs(
+        deps.storage,
+        filter,
+        from,
+        to,
+        start_after.map(|cursor| (cursor.block_height, cursor.log_index)),
+        limit as usize,
+    )?)
+}
 
 #[entry_point]
 #[allow(clippy::needless_pass_by_value)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
-) -> StdResult<Response> {
-    let sender = Address::from_private_key(
+) -> Result<Response, ContractError> {
+    record_block_hash(deps.storage, &env.block)?;
+
+    let canonical_sender = deps.api.addr_canonicalize(info.sender.as_str())?;
+
+    let sender = evm_address(&canonical_sender);
+
+    if deps.storage.read::<AddressMappingStore>(sender)?.is_none() {
+        deps.storage
+            .write::<AddressMappingStore>(sender, &canonical_sender);
+    }
+
+    let migrated = migrate_pending_legacy_account(deps.storage, &info.sender, sender)?;
+
+    let res =
+        match msg {
+            ExecuteMsg::Transaction(tx) => {
+                let mut evm = Evm::new(deps.storage, deps.api, deps.querier, &env)?;
+
+                transaction(&mut evm, sender, tx)
+            }
+            ExecuteMsg::RawTransaction(raw) => {
+                let mut evm = Evm::new(deps.storage, deps.api, deps.querier, &env)?;
+
+                raw_transaction(&mut evm, deps.api, &raw)
+            }
+            ExecuteMsg::Lock => {
+                let config = deps
+                    .storage
+                    .read::<ConfigStore>(())?
+                    .ok_or(ContractError::MissingConfig)?;
+
+                let funds = info
+                    .funds
+                    .iter()
+                    .find(|c| c.denom == config.denom)
+                    .ok_or_else(|| ContractError::MissingFunds {
+                        denom: config.denom.clone(),
+                    })?;
+
+                let account = deps
+                    .storage
+                    .read::<AccountInfoStore>(sender)?
+                    .unwrap_or_default();
+
+                write_account(
+                    deps.storage,
+                    sender,
+                    &RawAccountInfo {
+                        balance: (U256::from_be_bytes::<32>(account.balance)
+                            + U256::from(funds.amount.u128()))
+                        .to_be_bytes(),
+                        nonce: account.nonce,
+                        code_hash: account.code_hash,
+                    },
+                )?;
+
+                Ok(Response::new().add_event(
+                    Event::new("lock")
+                        .add_attribute("ether", funds.amount)
+                        .add_attribute("address", sender.to_string()),
+                ))
+            }
+            ExecuteMsg::Unlock(eth) => {
+                let eth_ = U256::from_be_bytes(eth.to_be_bytes());
+
+                let config = deps
+                    .storage
+                    .read::<ConfigStore>(())?
+                    .ok_or(ContractError::MissingConfig)?;
+
+                let account = deps
+                    .storage
+                    .read::<AccountInfoStore>(sender)?
+                    .unwrap_or_default();
+
+                let current_balance = U256::from_be_bytes::<32>(account.balance);
+
+                if current_balance < eth_ {
+                    return Err(ContractError::InsufficientBalance {
+                        balance: Uint256::from_be_bytes(current_balance.to_be_bytes()),
+                        required: eth,
+                    });
+                }
+
+                write_account(
+                    deps.storage,
+                    sender,
+                    &RawAccountInfo {
+                        balance: (current_balance - eth_).to_be_bytes(),
+                        nonce: account.nonce,
+                        code_hash: account.code_hash,
+                    },
+                )?;
+
+                Ok(Response::new()
+                    .add_event(
+                        Event::new("unlock")
+                            .add_attribute("ether", Uint256::from_be_bytes(eth_.to_be_bytes()))
+                            .add_attribute("address", sender.to_string()),
+                    )
+                    .add_submessage(SubMsg::reply_never(CosmosMsg::Bank(
+                        cosmwasm_std::BankMsg::Send {
+                            to_address: info.sender.to_string(),
+                            amount: vec![Coin::new(
+                                Uint128::try_from(eth).map_err(StdError::from)?,
+                                config.denom,
+                            )],
+                        },
+                    ))))
+            }
+            ExecuteMsg::MigrateLegacyAccount => Ok(Response::new()
+                .add_event(migrate_legacy_account(deps.storage, &info.sender, sender)?)),
+        }?;
+
+    Ok(res.add_events(migrated))
+}
+
+/// Migrates the legacy account of `cosmos_sender` if it has not been migrated since the contract was
+/// migrated from the legacy address derivation scheme.
+fn migrate_pending_legacy_account(
+    storage: &mut dyn Storage,
+    cosmos_sender: &cosmwasm_std::Addr,
+    sender: Address,
+) -> Result<Option<Event>, ContractError> {
+    if storage
+        .read::<LegacyAccountStore>(legacy_evm_address(cosmos_sender))?
+        .is_none()
+    {
+        return Ok(None);
+    }
+
+    migrate_legacy_account(storage, cosmos_sender, sender).map(Some)
+}
+
+/// Moves the balance of the legacy address of `cosmos_sender` to its current address `sender`.
+fn migrate_legacy_account(
+    storage: &mut dyn Storage,
+    cosmos_sender: &cosmwasm_std::Addr,
+    sender: Address,
+) -> Result<Event, ContractError> {
+    let legacy_address = legacy_evm_address(cosmos_sender);
+
+    storage.delete::<LegacyAccountStore>(legacy_address);
+
+    let legacy_account = storage.read::<AccountInfoStore>(legacy_address)?;
+
+    let legacy_balance = legacy_account.map_or(U256::ZERO, |account| {
+        U256::from_be_bytes::<32>(account.balance)
+    });
+
+    // without a balance to move, writing the accounts would only add empty accounts to the state
+    if let Some(legacy_account) = legacy_account
+        && !legacy_balance.is_zero()
+    {
+        let account = storage
+            .read::<AccountInfoStore>(sender)?
+            .unwrap_or_default();
+
+        write_account(
+            storage,
+            legacy_address,
+            &RawAccountInfo {
+                balance: U256::ZERO.to_be_bytes(),
+                nonce: legacy_account.nonce,
+                code_hash: legacy_account.code_hash,
+            },
+        )?;
+
+        write_account(
+            storage,
+            sender,
+            &RawAccountInfo {
+                balance: (U256::from_be_bytes::<32>(account.balance) + legacy_balance)
+                    .to_be_bytes(),
+                nonce: account.nonce,
+                code_hash: account.code_hash,
+            },
+        )?;
+    }
+
+    Ok(Event::new("migrate_legacy_account")
+        .add_attribute(
+            "ether",
+            Uint256::from_be_bytes(legacy_balance.to_be_bytes()),
+        )
+        .add_attribute("legacy_address", legacy_address.to_string())
+        .add_attribute("address", sender.to_string()))
+}
+
+/// The EVM address of a cosmos account, derived from the keccak256 of its canonical address.
+///
+/// There is no known private key for this address, so the account can only be controlled through
+/// this contract by the cosmos account itself.
+#[must_use]
+pub fn evm_address(canonical_address: &CanonicalAddr) -> Address {
+    Address::from_word(keccak256(canonical_address.as_slice()))
+}
+
+/// The EVM address of a cosmos account under the legacy derivation scheme, which used the sha256 of
+/// the bech32 address as a private key.
+///
+/// Anyone can recompute this key from the bech32 address, so this is only used to allow accounts to
+/// move their balance out of their legacy address with [`ExecuteMsg::MigrateLegacyAccount`].
+#[must_use]
+pub fn legacy_evm_address(address: &cosmwasm_std::Addr) -> Address {
+    Address::from_private_key(
         &Into::<[u8; 32]>::into(
             sha2::Sha256::new()
-                .chain_update(info.sender.as_bytes())
+                .chain_update(address.as_bytes())
                 .finalize(),
         )
         .as_slice()
         .try_into()
         .expect("32 bytes is a valid private key; qed;"),
-    );
+    )
+}
 
-    match msg {
-        ExecuteMsg::Transaction(tx) => {
-            let mut evm = Evm::new(deps.storage);
+pub fn transaction(evm: &mut Evm, sender: Address, tx: Tx) -> Result<Response, ContractError> {
+    let account = evm.evm.db_mut().basic(sender)?;
 
-            transaction(&mut evm, sender, tx)
-        }
-        ExecuteMsg::Lock => {
-            let config = deps
-                .storage
-                .read::<ConfigStore>(())
-                .expect("config must exist");
+    let nonce = account.map(|account| account.nonce).unwrap_or_default();
 
-            let funds = info
-                .funds
-                .iter()
-                .find(|c| c.denom == config.denom)
-                .ok_or_else(|| StdError::generic_err("no funds provided"))?;
+    if let Some(tx_nonce) = tx.nonce
+        && tx_nonce != nonce
+    {
+        return Err(ContractError::InvalidNonce {
+            expected: nonce,
+            found: tx_nonce,
+        });
+    }
 
-            let account = deps
-                .storage
-                .read::<AccountInfoStore>(sender)
-                .unwrap_or_default();
+    let gas_limit = evm.config.gas_limit();
+    fill_tx_env(evm.evm.tx_mut(), sender, tx, gas_limit);
 
-            deps.storage.write::<AccountInfoStore>(
-                sender,
-                &RawAccountInfo {
-                    balance: (U256::from_be_bytes::<32>(account.balance)
-                        + U256::from(funds.amount.u128()))
-                    .to_be_bytes(),
-                    nonce: account.nonce,
-                    code_hash: account.code_hash,
-                },
-            );
+    evm.evm.tx_mut().nonce = Some(nonce);
 
-            Ok(Response::new().add_event(
-                Event::new("lock")
-                    .add_attribute("ether", funds.amount)
-                    .add_attribute("address", sender.to_string()),
-            ))
-        }
-        ExecuteMsg::Unlock(eth) => {
-            let eth_ = U256::from_be_bytes(eth.to_be_bytes());
+    let hash = unsigned_tx_hash(evm.config.chain_id, evm.evm.tx());
 
-            let config = deps
-                .storage
-                .read::<ConfigStore>(())
-                .expect("config must exist");
+    commit(evm, hash)
+}
 
-            let account = deps
-                .storage
-                .read::<AccountInfoStore>(sender)
-                .unwrap_or_default();
+/// Executes a signed, RLP encoded transaction as the address that signed it.
+///
+/// Legacy transactions signed before EIP-155 are rejected, as they are valid on every chain.
+pub fn raw_transaction(
+    evm: &mut Evm,
+    api: &dyn Api,
+    raw: &[u8],
+) -> Result<Response, ContractError> {
+    let tx = SignedTx::decode(raw)?;
+
+    // without a chain ID, a transaction signed for any other chain could be replayed here
+    if let TypedTx::Legacy(TxLegacy { chain_id: None, .. }) = tx.tx {
+        return Err(ContractError::UnprotectedTransaction);
+    }
 
-            let current_balance = U256::from_be_bytes::<32>(account.balance);
+    let signer = tx.recover_signer(api)?;
 
-            if current_balance < eth_ {
-                return Err(StdError::overflow(OverflowError::new(
-                    OverflowOperation::Sub,
-                )));
-            }
+    // the private key of a legacy address is public, so only its owner may use the account, after
+    // moving its balance to their current address
+    if evm
+        .storage_mut()
+        .read::<LegacyAccountStore>(signer)?
+        .is_some()
+    {
+        return Err(ContractError::LegacyAccount { address: signer });
+    }
+
+    tx.tx.fill_tx_env(evm.evm.tx_mut(), signer);
+
+    commit(evm, tx.hash)
+}
+
+/// Executes the transaction currently set on `evm` and commits the resulting state changes.
+fn commit(evm: &mut Evm, hash: B256) -> Result<Response, ContractError> {
+    let sender = evm.evm.tx().caller;
+    let to = evm.evm.tx().transact_to.to().copied().map(Addr);
+    let nonce = evm
+        .evm
+        .tx()
+        .nonce
+        .expect("nonce is set for committed transactions; qed;");
+    let gas_limit = evm.evm.tx().gas_limit;
+    let gas_price = evm.evm.context.evm.env.effective_gas_price();
+
+    match evm.transact_commit()? {
+        ExecutionResult::Success {
+            reason,
+            gas_used,
+            gas_refunded,
+            logs,
+            output,
+        } => {
+            let block = evm.record_transaction(hash, gas_used, &logs)?;
+            let first_log_index = block.log_count - logs.len() as u64;
 
-            deps.storage.write::<AccountInfoStore>(
-                sender,
-                &RawAccountInfo {
-                    balance: (current_balance - eth_).to_be_bytes(),
-                    nonce: account.nonce,
-                    code_hash: account.code_hash,
+            let receipt = TransactionReceipt {
+                transaction_hash: H256(hash),
+                block_height: block.height,
+                transaction_index: block.transaction_count - 1,
+                from: Addr(sender),
+                to,
+                status: 1,
+                gas_used,
+                cumulative_gas_used: block.gas_used,
+                effective_gas_price: Uint256::from_be_bytes(gas_price.to_be_bytes()),
+                contract_address: match output {
+                    Output::Create(_, address) => address.map(Addr),
+                    Output::Call(_) => None,
                 },
-            );
+                logs: (first_log_index..)
+                    .zip(logs)
+                    .map(|(log_index, log)| Log {
+                        address: Addr(log.address),
+                        topics: log.topics().iter().copied().map(H256).collect(),
+                        data: log.data.data.to_vec().into(),
+                        block_height: block.height,
+                        transaction_hash: H256(hash),
+                        transaction_index: block.transaction_count - 1,
+                        log_index,
+                    })
+                    .collect(),
+            };
+
+            evm.storage_mut().write::<ReceiptStore>(hash, &receipt);
+            store_logs(evm.storage_mut(), &receipt.logs);
+
+            let messages = precompile_messages(evm, &receipt.logs)?;
 
             Ok(Response::new()
+                .add_attribute("transaction_hash", hash.to_string())
                 .add_event(
-                    Event::new("unlock")
-                        .add_attribute("ether", Uint256::from_be_bytes(eth_.to_be_bytes()))
-                        .add_attribute("address", sender.to_string()),
+                    Event::new("evm").add_attributes([
+                        ("caller", sender.to_string()),
+                        ("nonce", nonce.to_string()),
+                        (
+                            "reason",
+                            match reason {
+                                SuccessReason::Stop => "stop",
+                                SuccessReason::Return => "return",
+                                SuccessReason::SelfDestruct => "self_destruct",
+                                SuccessReason::EofReturnContract => "eof_return_contract",
+                            }
+                            .to_owned(),
+                        ),
+                        ("gas_limit", gas_limit.to_string()),
+                        ("gas_used", gas_used.to_string()),
+                        ("gas_refunded", gas_refunded.to_string()),
+                        ("gas_price", gas_price.to_string()),
+                        ("sdk_gas_used", evm.config.sdk_gas(gas_used).to_string()),
+                    ]),
                 )
-                .add_submessage(SubMsg::reply_never(CosmosMsg::Bank(
-                    cosmwasm_std::BankMsg::Send {
-                        to_address: info.sender.to_string(),
-                        amount: vec![Coin::new(Uint128::try_from(eth)?, config.denom)],
-                    },
-                ))))
+                .add_event(match output {
+                    Output::Call(bytes) => {
+                        Event::new("call").add_attribute("value", bytes.to_string())
+                    }
+                    Output::Create(bytes, address) => Event::new("create")
+                        .add_attribute("value", bytes.to_string())
+                        .add_attribute(
+                            "address",
+                            address.map(|a| a.to_string()).unwrap_or_default(),
+                        ),
+                })
+                .add_events(receipt.logs.iter().map(log_event))
+                .add_submessages(messages))
+        }
+        ExecutionResult::Revert { gas_used, output } => Err(ContractError::Revert {
+            reason: RevertReason::decode(&output),
+            data: output.to_vec().into(),
+            gas_used,
+        }),
+        ExecutionResult::Halt { reason, gas_used } => Err(halted(reason, gas_used, gas_limit)),
+    }
+}
+
+/// The cosmos messages queued by precompiles in `logs`, after burning the value the precompiles held
+/// for them. Executions awaiting a reply are recorded for [`reply`].
+fn precompile_messages(evm: &mut Evm, logs: &[Log]) -> Result<Vec<SubMsg>, ContractError> {
+    let messages = precompiles::messages(logs).collect::<Vec<_>>();
+
+    for address in [BANK_ADDRESS, WASM_ADDRESS] {
+        let total = messages
+            .iter()
+            .map(Message::escrow)
+            .filter(|(escrow, _)| *escrow == address)
+            .fold(U256::ZERO, |total, (_, amount)| {
+                total.saturating_add(amount)
+            });
+
+        if total.is_zero() {
+            continue;
+        }
+
+        let storage = evm.storage_mut();
+        let account = storage
+            .read::<AccountInfoStore>(address)?
+            .unwrap_or_default();
+
+        write_account(
+            storage,
+            address,
+            &RawAccountInfo {
+                balance: U256::from_be_bytes(account.balance)
+                    .saturating_sub(total)
+                    .to_be_bytes(),
+                ..account
+            },
+        )?;
+    }
+
+    let denom = evm.config.denom.clone();
+    let coins = |amount: U256| {
+        if amount.is_zero() {
+            vec![]
+        } else {
+            vec![Coin::new(amount.saturating_to::<u128>(), denom.clone())]
+        }
+    };
+
+    let mut submessages = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        submessages.push(match message {
+            Message::BankSend { to, amount } => {
+                SubMsg::reply_never(CosmosMsg::Bank(BankMsg::Send {
+                    to_address: to,
+                    amount: coins(amount),
+                }))
+            }
+            Message::WasmExecute {
+                id,
+                from,
+                contract,
+                msg,
+                funds,
+                reply,
+            } => {
+                let execute = CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract,
+                    msg: msg.into(),
+                    funds: coins(funds),
+                });
+
+                if reply {
+                    evm.storage_mut()
+                        .write::<WasmReplyStore>(id, &(from, funds));
+                    SubMsg::reply_always(execute, id)
+                } else {
+                    // a failed execution fails the whole transaction, restoring the burnt funds
+                    SubMsg::reply_never(execute)
+                }
+            }
+        });
+    }
+
+    Ok(submessages)
+}
+
+/// The event emitted for an EVM log.
+fn log_event(log: &Log) -> Event {
+    Event::new("log")
+        .add_attribute("address", log.address.0.to_string())
+        .add_attributes(
+            log.topics
+                .iter()
+                .enumerate()
+                .map(|(idx, topic)| (format!("topic{idx}"), topic.0.to_string())),
+        )
+        .add_attribute("data", format!("0x{}", log.data))
+}
+
+/// The error for a transaction that halted with `reason`.
+fn halted(reason: HaltReason, gas_used: u64, gas_limit: u64) -> ContractError {
+    match reason {
+        HaltReason::OutOfGas(_) => ContractError::OutOfGas { gas_limit },
+        reason => ContractError::Halt { reason, gas_used },
+    }
+}
+
+/// Simulates `tx` without committing any state changes, in the manner of `eth_call`.
+pub fn call<'a>(
+    evm: &mut Evm<'a, &'a dyn Storage>,
+    sender: Address,
+    tx: Tx,
+) -> Result<CallResponse, ContractError> {
+    let gas_limit = evm.config.gas_limit();
+    fill_tx_env(evm.evm.tx_mut(), sender, tx, gas_limit);
+    without_base_fee_if_free(evm);
+    let gas_limit = evm.evm.tx().gas_limit;
+
+    match evm.evm.transact()?.result {
+        ExecutionResult::Success {
+            gas_used, output, ..
+        } => Ok(CallResponse {
+            output: output.into_data().to_vec().into(),
+            gas_used,
+            revert: None,
+            revert_reason: None,
+        }),
+        ExecutionResult::Revert { gas_used, output } => Ok(CallResponse {
+            output: HexBinary::default(),
+            gas_used,
+            revert: Some(output.to_vec().into()),
+            revert_reason: Some(RevertReason::decode(&output)),
+        }),
+        ExecutionResult::Halt { reason, gas_used } => Err(halted(reason, gas_used, gas_limit)),
+    }
+}
+
+/// Estimates the lowest gas limit with which `tx` succeeds, in the manner of `eth_estimateGas`.
+///
+/// This follows geth's implementation: the transaction is first executed with the maximum gas
+/// limit, and then a binary search is performed between the gas used by that execution and the
+/// maximum gas limit, stopping once the bounds are within 1.5% of each other.
+pub fn estimate_gas<'a>(
+    evm: &mut Evm<'a, &'a dyn Storage>,
+    sender: Address,
+    tx: Tx,
+) -> Result<EstimateGasResponse, ContractError> {
+    let gas_limit = evm.config.gas_limit();
+    fill_tx_env(evm.evm.tx_mut(), sender, tx, gas_limit);
+    without_base_fee_if_free(evm);
+
+    let mut hi = evm.evm.tx().gas_limit;
+
+    // the gas limit is bounded by the fee the sender can pay
+    let gas_price = evm.evm.tx().gas_price;
+    if !gas_price.is_zero() {
+        let balance = evm
+            .evm
+            .db_mut()
+            .basic(sender)?
+            .map(|account| account.balance)
+            .unwrap_or_default();
+
+        hi =
+            hi.min((balance.saturating_sub(evm.evm.tx().value) / gas_price).saturating_to::<u64>());
+    }
+
+    let (mut lo, gas_refunded) = match simulate(evm, hi)? {
+        ExecutionResult::Success {
+            gas_used,
+            gas_refunded,
+            ..
+        } => (gas_used - 1, gas_refunded),
+        ExecutionResult::Revert { output, .. } => {
+            return Ok(EstimateGasResponse {
+                gas: None,
+                revert: Some(output.to_vec().into()),
+                revert_reason: Some(RevertReason::decode(&output)),
+            });
+        }
+        ExecutionResult::Halt { .. } => {
+            return Ok(EstimateGasResponse {
+                gas: None,
+                revert: None,
+                revert_reason: None,
+            });
+        }
+    };
+
+    // most transactions succeed with slightly more than the gas used (the extra being required for
+    // refunds and the 63/64 rule), so try that first before searching
+    let optimistic = (lo + 1)
+        .saturating_add(gas_refunded)
+        .saturating_add(CALL_STIPEND)
+        .saturating_mul(64)
+        / 63;
+
+    if optimistic < hi {
+        if simulate(evm, optimistic)?.is_success() {
+            hi = optimistic;
+        } else {
+            lo = optimistic;
         }
     }
+
+    while lo + 1 < hi {
+        if u128::from(hi - lo) * 1000 < u128::from(hi) * 15 {
+            break;
+        }
+
+        let mid = (lo + (hi - lo) / 2).min(lo.saturating_mul(2));
+
+        if simulate(evm, mid)?.is_success() {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+
+    Ok(EstimateGasResponse {
+        gas: Some(hi),
+        revert: None,
+        revert_reason: None,
+    })
+}
+
+/// Like geth, simulations without a gas price are executed with a base fee of zero, so that they are
+/// not rejected for paying less than the base fee.
+fn without_base_fee_if_free<'a>(evm: &mut Evm<'a, &'a dyn Storage>) {
+    if evm.evm.tx().gas_price.is_zero() {
+        evm.evm.block_mut().basefee = U256::ZERO;
+    }
 }
 
-pub fn transaction(evm: &mut Evm, sender: Address, tx: Tx) -> StdResult<Response> {
-    let tx_mut = evm.evm.tx_mut();
+fn simulate<'a>(
+    evm: &mut Evm<'a, &'a dyn Storage>,
+    gas_limit: u64,
+) -> Result<ExecutionResult, ContractError> {
+    evm.evm.tx_mut().gas_limit = gas_limit;
+
+    Ok(evm.evm.transact()?.result)
+}
 
+/// Sets `tx` as the transaction on `tx_mut`, with a gas limit of `max_gas_limit` unless `tx`
+/// specifies one.
+fn fill_tx_env(tx_mut: &mut TxEnv, sender: Address, tx: Tx, max_gas_limit: u64) {
     tx_mut.caller = sender;
-    tx_mut.gas_limit = u64::MAX;
-    tx_mut.gas_price = U256::from(0);
+    tx_mut.gas_limit = tx.gas.unwrap_or(max_gas_limit);
+    tx_mut.gas_price = tx
+        .max_fee_per_gas
+        .or(tx.gas_price)
+        .map(|price| U256::from_be_bytes(price.to_be_bytes()))
+        .unwrap_or_default();
+    tx_mut.gas_priority_fee = tx
+        .max_priority_fee_per_gas
+        .map(|fee| U256::from_be_bytes(fee.to_be_bytes()));
     tx_mut.transact_to = match tx.to {
         TxKind::Create => revm::primitives::TxKind::Create,
         TxKind::Call(addr) => revm::primitives::TxKind::Call(addr.0),
@@ -159,89 +955,239 @@ pub fn transaction(evm: &mut Evm, sender: Address, tx: Tx) -> StdResult<Response
         .map(|value| U256::from_be_bytes(value.to_be_bytes()))
         .unwrap_or_default();
     tx_mut.data = tx.input.unwrap_or_default().to_vec().into();
+    tx_mut.nonce = tx.nonce;
+    tx_mut.chain_id = tx.chain_id;
+}
 
-    let res = evm.evm.transact_commit();
+#[entry_point]
+#[allow(clippy::needless_pass_by_value)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::SetBlockHash { height, hash } => {
+            // hashes are stored by height modulo 256, so any other height would overwrite the hash
+            // of one of the last 256 blocks
+            if height > env.block.height || env.block.height - height >= 256 {
+                return Err(ContractError::BlockOutOfRange { height });
+            }
 
-    match res {
-        Ok(ExecutionResult::Success {
-            reason,
-            gas_used,
-            gas_refunded,
-            logs,
-            output,
-        }) => Ok(Response::new()
-            .add_event(
-                Event::new("evm").add_attributes([
-                    ("caller", sender.to_string()),
-                    (
-                        "reason",
-                        match reason {
-                            SuccessReason::Stop => "stop",
-                            SuccessReason::Return => "return",
-                            SuccessReason::SelfDestruct => "self_destruct",
-                            SuccessReason::EofReturnContract => "eof_return_contract",
-                        }
-                        .to_owned(),
-                    ),
-                    ("gas_used", gas_used.to_string()),
-                    ("gas_refunded", gas_refunded.to_string()),
-                ]),
-            )
-            .add_event(match output {
-                Output::Call(bytes) => Event::new("call").add_attribute("value", bytes.to_string()),
-                Output::Create(bytes, address) => Event::new("create")
-                    .add_attribute("value", bytes.to_string())
-                    .add_attribute(
-                        "address",
-                        address.map(|a| a.to_string()).unwrap_or_default(),
-                    ),
-            })
-            .add_events(logs.into_iter().map(|log| {
-                Event::new("log")
-                    .add_attribute("address", log.address.to_string())
-                    .add_attributes(
-                        log.topics()
-                            .iter()
-                            .enumerate()
-                            .map(|(idx, topic)| (format!("topic{idx}"), topic.to_string())),
-                    )
-                    .add_attribute("data", log.data.data.to_string())
-            }))),
-        Ok(res) => Err(StdError::generic_err(
-            serde_json::to_string(&res).expect("infallible"),
-        )),
-        Err(err) => Err(StdError::generic_err(err.to_string())),
+            deps.storage.write::<BlockHashStore>(
+                height,
+                &RawBlockHash {
+                    height,
+                    hash: hash.0.into(),
+                },
+            );
+
+            Ok(Response::new().add_event(
+                Event::new("set_block_hash")
+                    .add_attribute("height", height.to_string())
+                    .add_attribute("hash", hash.0.to_string()),
+            ))
+        }
     }
 }
 
+/// Passes the result of an execution queued with the wasm precompile to the EVM contract that queued
+/// it, with a call to `IWasmCallback.onWasmReply`. The funds of a failed execution are refunded to
+/// the contract first.
 #[entry_point]
-pub fn migrate(_: DepsMut, _: Env, _: MigrateMsg) -> StdResult<Response> {
-    Ok(Response::default())
-}
+#[allow(clippy::needless_pass_by_value)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    record_block_hash(deps.storage, &env.block)?;
 
-#[cfg(test)]
-mod tests {
-    use cosmwasm_std::{
-        Coin,
-        testing::{message_info, mock_dependencies, mock_env},
-    };
-    use revm::primitives::{address, hex};
+    let (caller, funds) = deps
+        .storage
+        .read::<WasmReplyStore>(msg.id)?
+        .ok_or(ContractError::UnknownReply { id: msg.id })?;
 
-    use crate::{
-        contract::execute,
-        msg::{Addr, Tx, TxKind},
-    };
+    deps.storage.delete::<WasmReplyStore>(msg.id);
 
-    use super::*;
+    let (success, data) = match msg.result.into_result() {
+        #[allow(deprecated)]
+        Ok(response) => (
+            true,
+            response
+                .msg_responses
+                .into_iter()
+                .next()
+                .map(|response| response.value)
+                .or(response.data)
+                .unwrap_or_default()
+                .to_vec(),
+        ),
+        Err(err) => {
+            // the failed execution returned the funds to this contract, so they go back to the
+            // caller, which burnt them when queueing it
+            if !funds.is_zero() {
+                let account = deps
+                    .storage
+                    .read::<AccountInfoStore>(caller)?
+                    .unwrap_or_default();
 
-    #[test]
-    fn exec() {
-        let mut deps = mock_dependencies();
-        let env = mock_env();
-        let info = message_info(&deps.api.addr_make(""), &[Coin::new(100_u128, "denom")]);
+                write_account(
+                    deps.storage,
+                    caller,
+                    &RawAccountInfo {
+                        balance: (U256::from_be_bytes(account.balance) + funds).to_be_bytes(),
+                        ..account
+                    },
+                )?;
+            }
 
-        let info_with_funds =
-            message_info(&deps.api.addr_make(""), &[Coin::new(100_u128, "denom")]).clone();
+            (false, err.into_bytes())
+        }
+    };
+
+    let mut evm = Evm::new(deps.storage, deps.api, deps.querier, &env)?;
+
+    let nonce = evm
+        .evm
+        .db_mut()
+        .basic(WASM_ADDRESS)?
+        .map(|account| account.nonce)
+        .unwrap_or_default();
+
+    *evm.evm.tx_mut() = TxEnv {
+        caller: WASM_ADDRESS,
+        gas_limit: evm.config.gas_limit(),
+        transact_to: revm::primitives::TxKind::Call(caller),
+        data: precompiles::wasm_reply_input(msg.id, success, data).into(),
+        nonce: Some(nonce),
+        ..TxEnv::default()
+    };
+
+    // the callback pays no fees
+    evm.evm.block_mut().basefee = U256::ZERO;
+
+    let hash = unsigned_tx_hash(evm.config.chain_id, evm.evm.tx());
+
+    commit(&mut evm, hash)
+}
+
+/// Records the hash of the current block for the `BLOCKHASH` opcode, unless it has already been
+/// recorded or set with [`SudoMsg::SetBlockHash`].
+fn record_block_hash(storage: &mut dyn Storage, block: &BlockInfo) -> Result<(), ContractError> {
+    if storage
+        .read::<BlockHashStore>(block.height)?
+        .is_none_or(|block_hash| block_hash.height != block.height)
+    {
+        storage.write::<BlockHashStore>(
+            block.height,
+            &RawBlockHash {
+                height: block.height,
+                hash: block_hash(block).into(),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+/// Sets the account at `address` to `info`, keeping the state trie up to date.
+fn write_account(
+    storage: &mut dyn Storage,
+    address: Address,
+    info: &RawAccountInfo,
+) -> Result<(), ContractError> {
+    storage.write::<AccountInfoStore>(address, info);
+
+    Ok(trie::update_account(storage, address, info, [])?)
+}
+
+#[entry_point]
+#[allow(clippy::needless_pass_by_value)]
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    match deps.storage.read::<ConfigStore>(()) {
+        Ok(Some(_)) => return Ok(Response::default()),
+        Ok(None) => return Err(ContractError::MissingConfig),
+        // the legacy config is the raw denom, which is not valid JSON
+        Err(_) => {}
+    }
+
+    let denom = deps
+        .storage
+        .read::<LegacyConfigStore>(())?
+        .ok_or(ContractError::MissingConfig)?;
+
+    let config = new_config(
+        InstantiateMsg {
+            eth_token: denom,
+            chain_id: msg.chain_id.ok_or(ContractError::MissingChainId)?,
+            coinbase: msg.coinbase,
+            prevrandao: msg.prevrandao,
+            gas_ratio: msg.gas_ratio,
+            sdk_gas_limit: msg.sdk_gas_limit,
+            fee_collector: msg.fee_collector,
+            base_fee: msg.base_fee,
+            state_trie: None,
+        },
+        env.block.height,
+    );
+
+    deps.storage.write::<ConfigStore>((), &config);
+
+    // every account without code was created under the legacy address derivation scheme, or could
+    // have been, as transfers are indistinguishable from deposits at a legacy address
+    let mut legacy_accounts = vec![];
+    for account in state::accounts(deps.storage) {
+        let (address, info) = account?;
+
+        if B256::from(info.code_hash) == KECCAK_EMPTY {
+            legacy_accounts.push(address);
+        }
+    }
+
+    for address in &legacy_accounts {
+        deps.storage.write::<LegacyAccountStore>(*address, &());
+    }
+
+    Ok(Response::new().add_event(
+        Event::new("migrate_config")
+            .add_attribute("denom", config.denom)
+            .add_attribute("chain_id", config.chain_id.to_string())
+            .add_attribute("legacy_accounts", legacy_accounts.len().to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{
+        Coin, ContractResult, OwnedDeps, SubMsgResponse, SubMsgResult, SystemResult, WasmQuery,
+        testing::{MockApi, MockQuerier, MockStorage, message_info, mock_dependencies, mock_env},
+    };
+    use std::num::NonZeroU64;
+
+    use revm::primitives::{InvalidTransaction, KECCAK_EMPTY, address, hex};
+
+    use crate::{
+        abi,
+        contract::execute,
+        error::StorageError,
+        msg::{Block, Tx, TxKind},
+        precompiles::ADDR_ADDRESS,
+        state::{Store, TrieNodeStore},
+        tx::{AccessListEntry, TxEip1559, TxEip2930},
+    };
+
+    use super::*;
+
+    /// Initcode for a simple counter contract, with the constructor argument `1`.
+    const COUNTER: [u8; 1078] = hex!(
+        "6080604052348015600e575f80fd5b506040516104163803806104168339818101604052810190602e9190606b565b805f81905550506091565b5f80fd5b5f819050919050565b604d81603d565b81146056575f80fd5b50565b5f815190506065816046565b92915050565b5f60208284031215607d57607c6039565b5b5f6088848285016059565b91505092915050565b6103788061009e5f395ff3fe608060405234801561000f575f80fd5b506004361061004a575f3560e01c806306661abd1461004e5780636d4ce63c1461006c578063b3bcfa821461008a578063fc5842bd14610094575b5f80fd5b6100566100b0565b60405161006391906101e2565b60405180910390f35b6100746100b5565b60405161008191906101e2565b60405180910390f35b6100926100bd565b005b6100ae60048036038101906100a99190610235565b61010f565b005b5f5481565b5f8054905090565b60015f808282546100ce919061028d565b925050819055507f757fff3e831f63e329ee929d928e44a48df56c5abd902d2414c60211a993e37e5f5460405161010591906101e2565b60405180910390a1565b600a8160ff16111561015857806040517fe74246a900000000000000000000000000000000000000000000000000000000815260040161014f91906102cf565b60405180910390fd5b5b5f8160ff1611156101c75760015f8082825461017591906102e8565b925050819055507f3443590b7333fb7cfd5e65585c8a4c4100c345929865db522919623bf37e58085f546040516101ac91906101e2565b60405180910390a180806101bf9061031b565b915050610159565b50565b5f819050919050565b6101dc816101ca565b82525050565b5f6020820190506101f55f8301846101d3565b92915050565b5f80fd5b5f60ff82169050919050565b610214816101ff565b811461021e575f80fd5b50565b5f8135905061022f8161020b565b92915050565b5f6020828403121561024a576102496101fb565b5b5f61025784828501610221565b91505092915050565b7f4e487b71000000000000000000000000000000000000000000000000000000005f52601160045260245ffd5b5f610297826101ca565b91506102a2836101ca565b92508282039050818111156102ba576102b9610260565b5b92915050565b6102c9816101ff565b82525050565b5f6020820190506102e25f8301846102c0565b92915050565b5f6102f2826101ca565b91506102fd836101ca565b925082820190508082111561031557610314610260565b5b92915050565b5f610325826101ff565b91505f820361033757610336610260565b5b60018203905091905056fea2646970667358221220756fc4b018cad6c146571e79e451bd4b6acc78da96ede40416af47a594d271f064736f6c634300081a00330000000000000000000000000000000000000000000000000000000000000001"
+    );
+
+    const CHAIN_ID: u64 = 1337;
+
+    const COUNTER_ADDRESS: Address = address!("0x721a11fc7b0594cca4e91f3ad09883a417c15933");
+
+    #[test]
+    fn exec() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = message_info(&deps.api.addr_make(""), &[Coin::new(100_u128, "denom")]);
+
+        let info_with_funds =
+            message_info(&deps.api.addr_make(""), &[Coin::new(100_u128, "denom")]).clone();
 
         instantiate(
             deps.as_mut(),
@@ -249,6 +1195,14 @@ mod tests {
             info.clone(),
             InstantiateMsg {
                 eth_token: "denom".to_owned(),
+                chain_id: CHAIN_ID,
+                coinbase: None,
+                prevrandao: None,
+                gas_ratio: None,
+                sdk_gas_limit: None,
+                fee_collector: None,
+                base_fee: None,
+                state_trie: None,
             },
         )
         .unwrap();
@@ -271,9 +1225,13 @@ mod tests {
                 // from: Some(Addr(Address::default())),
                 to: TxKind::Create,
                 value: None,
-                input: Some(hex!("6080604052348015600e575f80fd5b506040516104163803806104168339818101604052810190602e9190606b565b805f81905550506091565b5f80fd5b5f819050919050565b604d81603d565b81146056575f80fd5b50565b5f815190506065816046565b92915050565b5f60208284031215607d57607c6039565b5b5f6088848285016059565b91505092915050565b6103788061009e5f395ff3fe608060405234801561000f575f80fd5b506004361061004a575f3560e01c806306661abd1461004e5780636d4ce63c1461006c578063b3bcfa821461008a578063fc5842bd14610094575b5f80fd5b6100566100b0565b60405161006391906101e2565b60405180910390f35b6100746100b5565b60405161008191906101e2565b60405180910390f35b6100926100bd565b005b6100ae60048036038101906100a99190610235565b61010f565b005b5f5481565b5f8054905090565b60015f808282546100ce919061028d565b925050819055507f757fff3e831f63e329ee929d928e44a48df56c5abd902d2414c60211a993e37e5f5460405161010591906101e2565b60405180910390a1565b600a8160ff16111561015857806040517fe74246a900000000000000000000000000000000000000000000000000000000815260040161014f91906102cf565b60405180910390fd5b5b5f8160ff1611156101c75760015f8082825461017591906102e8565b925050819055507f3443590b7333fb7cfd5e65585c8a4c4100c345929865db522919623bf37e58085f546040516101ac91906101e2565b60405180910390a180806101bf9061031b565b915050610159565b50565b5f819050919050565b6101dc816101ca565b82525050565b5f6020820190506101f55f8301846101d3565b92915050565b5f80fd5b5f60ff82169050919050565b610214816101ff565b811461021e575f80fd5b50565b5f8135905061022f8161020b565b92915050565b5f6020828403121561024a576102496101fb565b5b5f61025784828501610221565b91505092915050565b7f4e487b71000000000000000000000000000000000000000000000000000000005f52601160045260245ffd5b5f610297826101ca565b91506102a2836101ca565b92508282039050818111156102ba576102b9610260565b5b92915050565b6102c9816101ff565b82525050565b5f6020820190506102e25f8301846102c0565b92915050565b5f6102f2826101ca565b91506102fd836101ca565b925082820190508082111561031557610314610260565b5b92915050565b5f610325826101ff565b91505f820361033757610336610260565b5b60018203905091905056fea2646970667358221220756fc4b018cad6c146571e79e451bd4b6acc78da96ede40416af47a594d271f064736f6c634300081a00330000000000000000000000000000000000000000000000000000000000000001").into()),
+                input: Some(COUNTER.into()),
                 nonce: None,
                 chain_id: None,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
                 transaction_type: None,
             }),
         );
@@ -286,7 +1244,7 @@ mod tests {
             info,
             ExecuteMsg::Transaction(Tx {
                 // from: Some(Addr(Address::default())),
-                to: TxKind::Call(Addr(address!("0x1c080665c72c0b9306d4319c0cce4ed153579863"))),
+                to: TxKind::Call(Addr(COUNTER_ADDRESS)),
                 value: None,
                 input: Some(
                     hex!(
@@ -296,6 +1254,10 @@ mod tests {
                 ),
                 nonce: None,
                 chain_id: None,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
                 transaction_type: None,
             }),
         );
@@ -306,4 +1268,2870 @@ mod tests {
 
         // let msg = serde_json::from_str(r#"{"transaction":{"input":"","to":"create"}}"#);
     }
+
+    /// Instantiates the contract and deploys [`COUNTER`] to [`COUNTER_ADDRESS`].
+    fn deploy_counter() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+        deploy_counter_with_state_trie(None)
+    }
+
+    fn deploy_counter_with_state_trie(
+        state_trie: Option<bool>,
+    ) -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            InstantiateMsg {
+                eth_token: "denom".to_owned(),
+                chain_id: CHAIN_ID,
+                coinbase: None,
+                prevrandao: None,
+                gas_ratio: None,
+                sdk_gas_limit: None,
+                fee_collector: None,
+                base_fee: None,
+                state_trie,
+            },
+        )
+        .unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Create,
+                value: None,
+                input: Some(COUNTER.into()),
+                nonce: None,
+                chain_id: None,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            }),
+        )
+        .unwrap();
+
+        (deps, env)
+    }
+
+    #[test]
+    fn query_state() {
+        let (deps, env) = deploy_counter();
+
+        let account: AccountInfoResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::AccountInfo {
+                    address: Addr(COUNTER_ADDRESS),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(account.nonce, 1);
+
+        let code: HexBinary = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::Code {
+                    address: Addr(COUNTER_ADDRESS),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(revm::primitives::keccak256(&code), account.code_hash.0);
+
+        let code_by_hash: HexBinary = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::CodeByHash {
+                    hash: account.code_hash,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(code, code_by_hash);
+
+        let slot: H256 = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::Storage {
+                    address: Addr(COUNTER_ADDRESS),
+                    slot: H256(B256::ZERO),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(U256::from_be_bytes(slot.0.0), U256::from(1));
+    }
+
+    #[test]
+    fn call_does_not_commit() {
+        let (deps, env) = deploy_counter();
+
+        let call = |input: &[u8]| -> CallResponse {
+            cosmwasm_std::from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::Call {
+                        tx: Tx {
+                            to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                            value: None,
+                            input: Some(input.into()),
+                            nonce: None,
+                            chain_id: None,
+                            gas: None,
+                            gas_price: None,
+                            max_fee_per_gas: None,
+                            max_priority_fee_per_gas: None,
+                            transaction_type: None,
+                        },
+                        from: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        // add(5)
+        let res = call(&hex!(
+            "fc5842bd0000000000000000000000000000000000000000000000000000000000000005"
+        ));
+        assert_eq!(res.revert, None);
+        assert!(res.gas_used > 0);
+
+        // get()
+        let res = call(&hex!("6d4ce63c"));
+        assert_eq!(U256::from_be_slice(&res.output), U256::from(1));
+
+        // add(11) reverts with a custom error
+        let res = call(&hex!(
+            "fc5842bd000000000000000000000000000000000000000000000000000000000000000b"
+        ));
+        assert!(res.output.is_empty());
+        assert_eq!(res.revert.unwrap()[..4], hex!("e74246a9"));
+        assert!(matches!(
+            res.revert_reason,
+            Some(RevertReason::Custom { selector, .. }) if *selector == hex!("e74246a9")
+        ));
+    }
+
+    #[test]
+    fn estimate_gas() {
+        let (deps, env) = deploy_counter();
+
+        let estimate = |input: &[u8]| -> EstimateGasResponse {
+            cosmwasm_std::from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::EstimateGas {
+                        tx: Tx {
+                            to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                            value: None,
+                            input: Some(input.into()),
+                            nonce: None,
+                            chain_id: None,
+                            gas: None,
+                            gas_price: None,
+                            max_fee_per_gas: None,
+                            max_priority_fee_per_gas: None,
+                            transaction_type: None,
+                        },
+                        from: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        // add(5)
+        let input =
+            hex!("fc5842bd0000000000000000000000000000000000000000000000000000000000000005");
+
+        let gas = estimate(&input).gas.unwrap();
+
+        let mut evm = Evm::new(
+            deps.as_ref().storage,
+            deps.as_ref().api,
+            deps.as_ref().querier,
+            &env,
+        )
+        .unwrap();
+        fill_tx_env(
+            evm.evm.tx_mut(),
+            Address::ZERO,
+            Tx {
+                to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                value: None,
+                input: Some(input.into()),
+                nonce: None,
+                chain_id: None,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            },
+            u64::MAX,
+        );
+
+        assert!(simulate(&mut evm, gas).unwrap().is_success());
+        assert!(!simulate(&mut evm, gas * 95 / 100).unwrap().is_success());
+
+        // add(11) reverts regardless of the gas limit
+        let res = estimate(&hex!(
+            "fc5842bd000000000000000000000000000000000000000000000000000000000000000b"
+        ));
+        assert_eq!(res.gas, None);
+        assert_eq!(res.revert.unwrap()[..4], hex!("e74246a9"));
+    }
+
+    /// Signs `tx` with `key` and returns the RLP encoded signed transaction.
+    fn sign(tx: &TypedTx, key: &k256::ecdsa::SigningKey) -> Vec<u8> {
+        use alloy_rlp::Encodable;
+
+        let (signature, recid) = key
+            .sign_prehash_recoverable(tx.signing_hash().as_slice())
+            .unwrap();
+
+        let r = U256::from_be_slice(&signature.r().to_bytes());
+        let s = U256::from_be_slice(&signature.s().to_bytes());
+        let y_parity = recid.is_y_odd();
+
+        let mut out = vec![];
+
+        match tx {
+            TypedTx::Legacy(tx) => {
+                let v = tx.chain_id.map_or(27, |chain_id| chain_id * 2 + 35) + u64::from(y_parity);
+
+                let fields: [&dyn Encodable; 9] = [
+                    &tx.nonce,
+                    &tx.gas_price,
+                    &tx.gas_limit,
+                    &tx.to,
+                    &tx.value,
+                    &tx.input,
+                    &v,
+                    &r,
+                    &s,
+                ];
+
+                alloy_rlp::encode_list::<_, dyn Encodable>(&fields, &mut out);
+            }
+            TypedTx::Eip2930(tx) => {
+                let fields: [&dyn Encodable; 11] = [
+                    &tx.chain_id,
+                    &tx.nonce,
+                    &tx.gas_price,
+                    &tx.gas_limit,
+                    &tx.to,
+                    &tx.value,
+                    &tx.input,
+                    &tx.access_list,
+                    &y_parity,
+                    &r,
+                    &s,
+                ];
+
+                out.push(0x01);
+                alloy_rlp::encode_list::<_, dyn Encodable>(&fields, &mut out);
+            }
+            TypedTx::Eip1559(tx) => {
+                let fields: [&dyn Encodable; 12] = [
+                    &tx.chain_id,
+                    &tx.nonce,
+                    &tx.max_priority_fee_per_gas,
+                    &tx.max_fee_per_gas,
+                    &tx.gas_limit,
+                    &tx.to,
+                    &tx.value,
+                    &tx.input,
+                    &tx.access_list,
+                    &y_parity,
+                    &r,
+                    &s,
+                ];
+
+                out.push(0x02);
+                alloy_rlp::encode_list::<_, dyn Encodable>(&fields, &mut out);
+            }
+        }
+
+        out
+    }
+
+    #[test]
+    fn raw_transaction() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = message_info(&deps.api.addr_make("relayer"), &[]);
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            InstantiateMsg {
+                eth_token: "denom".to_owned(),
+                chain_id: CHAIN_ID,
+                coinbase: None,
+                prevrandao: None,
+                gas_ratio: None,
+                sdk_gas_limit: None,
+                fee_collector: None,
+                base_fee: None,
+                state_trie: None,
+            },
+        )
+        .unwrap();
+
+        let key = k256::ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+        let signer = Address::from_private_key(&key);
+        let counter = signer.create(0);
+
+        let create = TypedTx::Eip1559(TxEip1559 {
+            chain_id: CHAIN_ID,
+            nonce: 0,
+            max_priority_fee_per_gas: 0,
+            max_fee_per_gas: 0,
+            gas_limit: 1_000_000,
+            to: revm::primitives::TxKind::Create,
+            value: U256::ZERO,
+            input: COUNTER.into(),
+            access_list: vec![],
+        });
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::RawTransaction(sign(&create, &key).into()),
+        )
+        .unwrap();
+
+        // add(2)
+        let add = TypedTx::Legacy(TxLegacy {
+            chain_id: Some(CHAIN_ID),
+            nonce: 1,
+            gas_price: 0,
+            gas_limit: 1_000_000,
+            to: revm::primitives::TxKind::Call(counter),
+            value: U256::ZERO,
+            input: hex!("fc5842bd0000000000000000000000000000000000000000000000000000000000000002")
+                .into(),
+        });
+
+        let raw = sign(&add, &key);
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::RawTransaction(raw.clone().into()),
+        )
+        .unwrap();
+
+        // the receipt is stored under the hash of the signed transaction
+        let receipt = deps
+            .as_ref()
+            .storage
+            .read::<ReceiptStore>(keccak256(&raw))
+            .unwrap()
+            .unwrap();
+        assert_eq!(receipt.from, Addr(signer));
+
+        // replaying the same transaction fails
+        execute(
+            deps.as_mut(),
+            env,
+            info,
+            ExecuteMsg::RawTransaction(raw.into()),
+        )
+        .unwrap_err();
+
+        let storage = deps.as_ref().storage;
+
+        assert_eq!(
+            storage
+                .read::<AccountInfoStore>(signer)
+                .unwrap()
+                .unwrap()
+                .nonce,
+            2
+        );
+        assert_eq!(
+            storage
+                .read::<AccountStorageStore>((counter, U256::ZERO))
+                .unwrap()
+                .unwrap(),
+            U256::from(3)
+        );
+    }
+
+    #[test]
+    fn eip2930_raw_transaction() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make("relayer"), &[]);
+        let key = k256::ecdsa::SigningKey::from_slice(&[2; 32]).unwrap();
+        let signer = Address::from_private_key(&key);
+
+        // add(1), with and without the count slot of the counter in the access list
+        let add = |nonce, access_list| {
+            sign(
+                &TypedTx::Eip2930(TxEip2930 {
+                    chain_id: CHAIN_ID,
+                    nonce,
+                    gas_price: 0,
+                    gas_limit: 1_000_000,
+                    to: revm::primitives::TxKind::Call(COUNTER_ADDRESS),
+                    value: U256::ZERO,
+                    input: hex!(
+                        "fc5842bd0000000000000000000000000000000000000000000000000000000000000001"
+                    )
+                    .into(),
+                    access_list,
+                }),
+                &key,
+            )
+        };
+
+        let access_list = vec![AccessListEntry {
+            address: COUNTER_ADDRESS,
+            storage_keys: vec![B256::ZERO],
+        }];
+
+        let raw = add(0, access_list.clone());
+        let tx = SignedTx::decode(&raw).unwrap();
+        assert!(matches!(
+            &tx.tx,
+            TypedTx::Eip2930(tx) if tx.access_list == access_list
+        ));
+        assert_eq!(tx.recover_signer(&deps.api).unwrap(), signer);
+
+        let mut gas_used = vec![];
+        for raw in [raw, add(1, vec![])] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::RawTransaction(raw.clone().into()),
+            )
+            .unwrap();
+
+            let receipt = deps
+                .as_ref()
+                .storage
+                .read::<ReceiptStore>(keccak256(&raw))
+                .unwrap()
+                .unwrap();
+            assert_eq!(receipt.from, Addr(signer));
+            gas_used.push(receipt.gas_used);
+        }
+
+        // the access list costs 2400 for the address and 1900 for the slot, and saves the 2000 of
+        // the first cold access to the slot
+        assert_eq!(gas_used[0], gas_used[1] + 2400 + 1900 - 2000);
+    }
+
+    #[test]
+    fn unprotected_raw_transaction() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make("relayer"), &[]);
+        let key = k256::ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+
+        // a transaction signed before EIP-155, with a `v` of 27 or 28
+        let raw = sign(
+            &TypedTx::Legacy(TxLegacy {
+                chain_id: None,
+                nonce: 0,
+                gas_price: 0,
+                gas_limit: 1_000_000,
+                to: revm::primitives::TxKind::Call(COUNTER_ADDRESS),
+                value: U256::ZERO,
+                input: revm::primitives::Bytes::new(),
+            }),
+            &key,
+        );
+
+        let tx = SignedTx::decode(&raw).unwrap();
+        assert!(matches!(
+            tx.tx,
+            TypedTx::Legacy(TxLegacy { chain_id: None, .. })
+        ));
+
+        assert_eq!(
+            execute(
+                deps.as_mut(),
+                env,
+                info,
+                ExecuteMsg::RawTransaction(raw.into()),
+            )
+            .unwrap_err(),
+            ContractError::UnprotectedTransaction
+        );
+        assert!(
+            deps.as_ref()
+                .storage
+                .read::<AccountInfoStore>(Address::from_private_key(&key))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn migrate_config() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = message_info(&deps.api.addr_make("sender"), &[Coin::new(1_u128, "denom")]);
+
+        // the config as written by the first version of the contract
+        let storage: &mut dyn Storage = &mut deps.storage;
+        storage.write::<LegacyConfigStore>((), &"denom".to_owned());
+
+        assert!(matches!(
+            execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Lock).unwrap_err(),
+            ContractError::BadStorage(_)
+        ));
+
+        assert_eq!(
+            migrate(deps.as_mut(), env.clone(), MigrateMsg::default()).unwrap_err(),
+            ContractError::MissingChainId
+        );
+
+        migrate(
+            deps.as_mut(),
+            env.clone(),
+            MigrateMsg {
+                chain_id: Some(CHAIN_ID),
+                ..MigrateMsg::default()
+            },
+        )
+        .unwrap();
+
+        let config = Config {
+            denom: "denom".to_owned(),
+            chain_id: CHAIN_ID,
+            coinbase: Address::ZERO,
+            prevrandao: RandomnessSource::BlockHash,
+            gas_ratio: DEFAULT_GAS_RATIO,
+            sdk_gas_limit: DEFAULT_SDK_GAS_LIMIT,
+            fee_collector: Address::ZERO,
+            base_fee: U256::ZERO,
+            base_fee_height: env.block.height,
+        };
+        assert_eq!(
+            deps.as_ref().storage.read::<ConfigStore>(()).unwrap(),
+            Some(config.clone())
+        );
+
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Lock).unwrap();
+
+        // a current config is left as is
+        migrate(
+            deps.as_mut(),
+            env,
+            MigrateMsg {
+                chain_id: Some(CHAIN_ID + 1),
+                ..MigrateMsg::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            deps.as_ref().storage.read::<ConfigStore>(()).unwrap(),
+            Some(config)
+        );
+    }
+
+    #[test]
+    fn migrate_legacy_accounts() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let sender = deps.api.addr_make("sender");
+        let info = message_info(&sender, &[Coin::new(1_u128, "denom")]);
+
+        let legacy_address = legacy_evm_address(&sender);
+        let contract_address = address!("0000000000000000000000000000000000001234");
+
+        let storage: &mut dyn Storage = &mut deps.storage;
+        storage.write::<LegacyConfigStore>((), &"denom".to_owned());
+        storage.write::<AccountInfoStore>(
+            legacy_address,
+            &RawAccountInfo::new(U256::from(100), 3, KECCAK_EMPTY),
+        );
+        storage.write::<AccountInfoStore>(
+            contract_address,
+            &RawAccountInfo::new(U256::ZERO, 1, B256::repeat_byte(1)),
+        );
+
+        migrate(
+            deps.as_mut(),
+            env.clone(),
+            MigrateMsg {
+                chain_id: Some(CHAIN_ID),
+                ..MigrateMsg::default()
+            },
+        )
+        .unwrap();
+
+        let storage = deps.as_ref().storage;
+        assert_eq!(
+            storage.read::<LegacyAccountStore>(legacy_address).unwrap(),
+            Some(())
+        );
+        assert_eq!(
+            storage
+                .read::<LegacyAccountStore>(contract_address)
+                .unwrap(),
+            None
+        );
+
+        // the first message of the owner moves the legacy balance
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::Lock).unwrap();
+        assert!(res.events.iter().any(|e| e.ty == "migrate_legacy_account"));
+
+        let storage = deps.as_ref().storage;
+        assert_eq!(
+            storage.read::<LegacyAccountStore>(legacy_address).unwrap(),
+            None
+        );
+
+        let legacy_account = storage
+            .read::<AccountInfoStore>(legacy_address)
+            .unwrap()
+            .unwrap();
+        assert_eq!(legacy_account.balance, [0; 32]);
+
+        let account = storage
+            .read::<AccountInfoStore>(evm_address(
+                &deps.api.addr_canonicalize(sender.as_str()).unwrap(),
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(U256::from_be_bytes(account.balance), U256::from(101));
+    }
+
+    #[test]
+    fn legacy_raw_transaction() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let owner = deps.api.addr_make("owner");
+        let relayer = message_info(&deps.api.addr_make("relayer"), &[]);
+
+        // the private key of the legacy address, which anyone can derive
+        let key = k256::ecdsa::SigningKey::from_slice(
+            &sha2::Sha256::new()
+                .chain_update(owner.as_bytes())
+                .finalize(),
+        )
+        .unwrap();
+        let legacy_address = legacy_evm_address(&owner);
+        assert_eq!(Address::from_private_key(&key), legacy_address);
+
+        let storage: &mut dyn Storage = &mut deps.storage;
+        storage.write::<LegacyConfigStore>((), &"denom".to_owned());
+        storage.write::<AccountInfoStore>(
+            legacy_address,
+            &RawAccountInfo::new(U256::from(100), 0, KECCAK_EMPTY),
+        );
+
+        migrate(
+            deps.as_mut(),
+            env.clone(),
+            MigrateMsg {
+                chain_id: Some(CHAIN_ID),
+                ..MigrateMsg::default()
+            },
+        )
+        .unwrap();
+
+        let raw = sign(
+            &TypedTx::Legacy(TxLegacy {
+                chain_id: Some(CHAIN_ID),
+                nonce: 0,
+                gas_price: 0,
+                gas_limit: 1_000_000,
+                to: revm::primitives::TxKind::Call(Address::ZERO),
+                value: U256::from(100),
+                input: revm::primitives::Bytes::new(),
+            }),
+            &key,
+        );
+
+        assert_eq!(
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                relayer.clone(),
+                ExecuteMsg::RawTransaction(raw.clone().into()),
+            )
+            .unwrap_err(),
+            ContractError::LegacyAccount {
+                address: legacy_address
+            }
+        );
+
+        // once the owner has migrated, the legacy address is an empty account like any other
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::MigrateLegacyAccount,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            execute(
+                deps.as_mut(),
+                env,
+                relayer,
+                ExecuteMsg::RawTransaction(raw.into())
+            )
+            .unwrap_err(),
+            ContractError::InvalidTransaction(_)
+        ));
+    }
+
+    #[test]
+    fn address_mapping() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let sender = deps.api.addr_make("sender");
+        let info = message_info(&sender, &[]);
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            InstantiateMsg {
+                eth_token: "denom".to_owned(),
+                chain_id: CHAIN_ID,
+                coinbase: None,
+                prevrandao: None,
+                gas_ratio: None,
+                sdk_gas_limit: None,
+                fee_collector: None,
+                base_fee: None,
+                state_trie: None,
+            },
+        )
+        .unwrap();
+
+        let legacy_address = legacy_evm_address(&sender);
+
+        deps.as_mut().storage.write::<AccountInfoStore>(
+            legacy_address,
+            &RawAccountInfo::new(U256::from(100), 3, KECCAK_EMPTY),
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::MigrateLegacyAccount,
+        )
+        .unwrap();
+
+        let address: Addr = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::EvmAddress {
+                    address: sender.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let cosmos_address: Option<cosmwasm_std::Addr> = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::CosmosAddress {
+                    address: address.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(cosmos_address, Some(sender));
+
+        let storage = deps.as_ref().storage;
+
+        let legacy_account = storage
+            .read::<AccountInfoStore>(legacy_address)
+            .unwrap()
+            .unwrap();
+        assert_eq!(legacy_account.balance, [0; 32]);
+        assert_eq!(legacy_account.nonce, 3);
+
+        let account = storage
+            .read::<AccountInfoStore>(address.0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(U256::from_be_bytes(account.balance), U256::from(100));
+        assert_eq!(account.nonce, 0);
+    }
+
+    #[test]
+    fn migrate_missing_legacy_account() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let sender = deps.api.addr_make("sender");
+        let info = message_info(&sender, &[]);
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            InstantiateMsg {
+                eth_token: "denom".to_owned(),
+                chain_id: CHAIN_ID,
+                coinbase: None,
+                prevrandao: None,
+                gas_ratio: None,
+                sdk_gas_limit: None,
+                fee_collector: None,
+                base_fee: None,
+                state_trie: Some(true),
+            },
+        )
+        .unwrap();
+
+        let state_root = deps.as_ref().storage.read::<StateRootStore>(()).unwrap();
+
+        execute(deps.as_mut(), env, info, ExecuteMsg::MigrateLegacyAccount).unwrap();
+
+        // nothing is moved, so no account is created
+        let storage = deps.as_ref().storage;
+        assert!(
+            storage
+                .read::<AccountInfoStore>(legacy_evm_address(&sender))
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            storage
+                .read::<AccountInfoStore>(evm_address(
+                    &deps.api.addr_canonicalize(sender.as_str()).unwrap()
+                ))
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(storage.read::<StateRootStore>(()).unwrap(), state_root);
+    }
+
+    #[test]
+    fn nonces() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        let add = |nonce| {
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                value: None,
+                input: Some(
+                    hex!(
+                        "fc5842bd0000000000000000000000000000000000000000000000000000000000000001"
+                    )
+                    .into(),
+                ),
+                nonce,
+                chain_id: None,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            })
+        };
+
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), add(Some(0))).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidNonce {
+                expected: 1,
+                found: 0
+            }
+        );
+
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), add(Some(1))).unwrap();
+        assert!(res.events[0].attributes.contains(&("nonce", "1").into()));
+
+        let res = execute(deps.as_mut(), env, info, add(None)).unwrap();
+        assert!(res.events[0].attributes.contains(&("nonce", "2").into()));
+    }
+
+    #[test]
+    fn chain_id() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        let get = |chain_id| {
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                value: None,
+                input: Some(hex!("6d4ce63c").into()),
+                nonce: None,
+                chain_id,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            })
+        };
+
+        assert_eq!(
+            execute(deps.as_mut(), env.clone(), info.clone(), get(Some(1))).unwrap_err(),
+            ContractError::InvalidTransaction(InvalidTransaction::InvalidChainId)
+        );
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            get(Some(CHAIN_ID)),
+        )
+        .unwrap();
+        execute(deps.as_mut(), env, info, get(None)).unwrap();
+    }
+
+    #[test]
+    fn block_env() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        let coinbase = address!("0x000000000000000000000000000000000000c0de");
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            InstantiateMsg {
+                eth_token: "denom".to_owned(),
+                chain_id: CHAIN_ID,
+                coinbase: Some(Addr(coinbase)),
+                prevrandao: None,
+                gas_ratio: None,
+                sdk_gas_limit: None,
+                fee_collector: None,
+                base_fee: None,
+                state_trie: None,
+            },
+        )
+        .unwrap();
+
+        // returns the result of `opcode` as the code of the created contract
+        let read = |opcode: u8| -> B256 {
+            let res: CallResponse = cosmwasm_std::from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::Call {
+                        tx: Tx {
+                            to: TxKind::Create,
+                            value: None,
+                            // <opcode> PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN
+                            input: Some([opcode, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3].into()),
+                            nonce: None,
+                            chain_id: None,
+                            gas: None,
+                            gas_price: None,
+                            max_fee_per_gas: None,
+                            max_priority_fee_per_gas: None,
+                            transaction_type: None,
+                        },
+                        from: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+            B256::from_slice(&res.output)
+        };
+
+        // NUMBER
+        assert_eq!(read(0x43), B256::from(U256::from(env.block.height)));
+        // TIMESTAMP
+        assert_eq!(read(0x42), B256::from(U256::from(env.block.time.seconds())));
+        // COINBASE
+        assert_eq!(read(0x41), coinbase.into_word());
+        // PREVRANDAO
+        assert_eq!(read(0x44), crate::evm::block_hash(&env.block));
+        // CHAINID
+        assert_eq!(read(0x46), B256::from(U256::from(CHAIN_ID)));
+    }
+
+    #[test]
+    fn blockhash() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            InstantiateMsg {
+                eth_token: "denom".to_owned(),
+                chain_id: CHAIN_ID,
+                coinbase: None,
+                prevrandao: None,
+                gas_ratio: None,
+                sdk_gas_limit: None,
+                fee_collector: None,
+                base_fee: None,
+                state_trie: None,
+            },
+        )
+        .unwrap();
+
+        let height = env.block.height;
+        let recorded = crate::evm::block_hash(&env.block);
+
+        // any execution records the hash of the current block
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::MigrateLegacyAccount,
+        )
+        .unwrap();
+
+        let set = B256::repeat_byte(0xaa);
+
+        sudo(
+            deps.as_mut(),
+            env.clone(),
+            SudoMsg::SetBlockHash {
+                height: height - 1,
+                hash: H256(set),
+            },
+        )
+        .unwrap();
+
+        env.block.height += 1;
+
+        let blockhash = |number: u64| -> B256 {
+            let res: CallResponse = cosmwasm_std::from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::Call {
+                        tx: Tx {
+                            to: TxKind::Create,
+                            value: None,
+                            // PUSH8 <number> BLOCKHASH PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN
+                            input: Some(
+                                [
+                                    &[0x67][..],
+                                    &number.to_be_bytes(),
+                                    &[0x40, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3],
+                                ]
+                                .concat()
+                                .into(),
+                            ),
+                            nonce: None,
+                            chain_id: None,
+                            gas: None,
+                            gas_price: None,
+                            max_fee_per_gas: None,
+                            max_priority_fee_per_gas: None,
+                            transaction_type: None,
+                        },
+                        from: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+            B256::from_slice(&res.output)
+        };
+
+        assert_eq!(blockhash(height), recorded);
+        assert_eq!(blockhash(height - 1), set);
+        assert_eq!(blockhash(height - 2), B256::ZERO);
+        assert_eq!(blockhash(height + 1), B256::ZERO);
+    }
+
+    #[test]
+    fn set_block_hash_range() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let height = env.block.height;
+
+        let mut set_block_hash = |height| {
+            sudo(
+                deps.as_mut(),
+                env.clone(),
+                SudoMsg::SetBlockHash {
+                    height,
+                    hash: H256(B256::repeat_byte(0xaa)),
+                },
+            )
+        };
+
+        set_block_hash(height).unwrap();
+        set_block_hash(height - 255).unwrap();
+
+        assert_eq!(
+            set_block_hash(height + 1).unwrap_err(),
+            ContractError::BlockOutOfRange { height: height + 1 }
+        );
+        assert_eq!(
+            set_block_hash(height - 256).unwrap_err(),
+            ContractError::BlockOutOfRange {
+                height: height - 256
+            }
+        );
+    }
+
+    #[test]
+    fn revert_reasons() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        // add(11) reverts with `TooLarge(11)`
+        let err = execute(
+            deps.as_mut(),
+            env,
+            info,
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                value: None,
+                input: Some(
+                    hex!(
+                        "fc5842bd000000000000000000000000000000000000000000000000000000000000000b"
+                    )
+                    .into(),
+                ),
+                nonce: None,
+                chain_id: None,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            }),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            &err,
+            ContractError::Revert {
+                reason: RevertReason::Custom { selector, data },
+                ..
+            } if *selector == hex!("e74246a9") && *data == U256::from(11).to_be_bytes::<32>()
+        ));
+        assert!(
+            err.to_string()
+                .starts_with("execution reverted: custom error 0xe74246a9")
+        );
+
+        // revert("nope")
+        assert_eq!(
+            RevertReason::decode(&hex!(
+                "08c379a0"
+                "0000000000000000000000000000000000000000000000000000000000000020"
+                "0000000000000000000000000000000000000000000000000000000000000004"
+                "6e6f706500000000000000000000000000000000000000000000000000000000"
+            )),
+            RevertReason::Error {
+                message: "nope".to_owned()
+            }
+        );
+
+        // arithmetic overflow
+        assert_eq!(
+            RevertReason::decode(&hex!(
+                "4e487b71"
+                "0000000000000000000000000000000000000000000000000000000000000011"
+            )),
+            RevertReason::Panic {
+                code: Uint256::from(0x11_u8),
+                description: "arithmetic underflow or overflow".to_owned()
+            }
+        );
+
+        // a truncated `Error(string)` is not decoded
+        assert_eq!(
+            RevertReason::decode(&hex!(
+                "08c379a0"
+                "0000000000000000000000000000000000000000000000000000000000000020"
+            )),
+            RevertReason::Custom {
+                selector: hex!("08c379a0").into(),
+                data: hex!("0000000000000000000000000000000000000000000000000000000000000020")
+                    .into(),
+            }
+        );
+
+        assert_eq!(
+            RevertReason::decode(&[]),
+            RevertReason::Raw {
+                data: HexBinary::default()
+            }
+        );
+    }
+
+    #[test]
+    fn bad_storage() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        let key = AccountInfoStore::encode_key(COUNTER_ADDRESS)
+            .as_ref()
+            .to_vec();
+        deps.storage.set(&key, &[0; 5]);
+
+        let expected = ContractError::BadStorage(StorageError {
+            key: key.into(),
+            source: StdError::invalid_data_size(72, 5),
+        });
+
+        let err = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Nonce {
+                address: Addr(COUNTER_ADDRESS),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, expected);
+
+        // the error is surfaced by the EVM as a database error
+        let err = execute(
+            deps.as_mut(),
+            env,
+            info,
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                value: None,
+                input: Some(hex!("6d4ce63c").into()),
+                nonce: None,
+                chain_id: None,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(err, expected);
+    }
+
+    #[test]
+    fn gas_limits() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        let tx = |input: &[u8], gas| {
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Create,
+                value: None,
+                input: Some(input.into()),
+                nonce: None,
+                chain_id: None,
+                gas,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            })
+        };
+
+        // JUMPDEST PUSH0 JUMP
+        let infinite_loop = hex!("5b5f56");
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            tx(&infinite_loop, Some(1_000_000)),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::OutOfGas {
+                gas_limit: 1_000_000
+            }
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            tx(&infinite_loop, Some(DEFAULT_SDK_GAS_LIMIT + 1)),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidTransaction(InvalidTransaction::CallerGasLimitMoreThanBlock)
+        );
+
+        let storage = deps.as_mut().storage;
+        let mut config = storage.read::<ConfigStore>(()).unwrap().unwrap();
+        config.gas_ratio = NonZeroU64::new(3).unwrap();
+        storage.write::<ConfigStore>((), &config);
+
+        // PUSH0 PUSH0 RETURN
+        let res = execute(deps.as_mut(), env, info, tx(&hex!("5f5ff3"), None)).unwrap();
+        let attr = |key| {
+            res.events[0]
+                .attributes
+                .iter()
+                .find(|attr| attr.key == key)
+                .unwrap()
+                .value
+                .parse::<u64>()
+                .unwrap()
+        };
+
+        assert_eq!(attr("gas_limit"), DEFAULT_SDK_GAS_LIMIT * 3);
+        assert_eq!(attr("sdk_gas_used"), attr("gas_used").div_ceil(3));
+    }
+
+    #[test]
+    fn gas_fees() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+        let sender = evm_address(&deps.api.addr_canonicalize(info.sender.as_str()).unwrap());
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&info.sender, &[Coin::new(1_000_000_000_u128, "denom")]),
+            ExecuteMsg::Lock,
+        )
+        .unwrap();
+
+        let balance = |deps: &OwnedDeps<_, _, _>, address| -> u128 {
+            cosmwasm_std::from_json::<Uint256>(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::Balance {
+                        address: Addr(address),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+            .try_into()
+            .map(|balance: Uint128| balance.u128())
+            .unwrap()
+        };
+
+        let add = |gas_price: Option<u128>,
+                   max_fee_per_gas: Option<u128>,
+                   max_priority_fee_per_gas: Option<u128>| {
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                value: None,
+                input: Some(
+                    hex!(
+                        "fc5842bd0000000000000000000000000000000000000000000000000000000000000001"
+                    )
+                    .into(),
+                ),
+                nonce: None,
+                chain_id: None,
+                gas: Some(100_000),
+                gas_price: gas_price.map(Uint256::from_u128),
+                max_fee_per_gas: max_fee_per_gas.map(Uint256::from_u128),
+                max_priority_fee_per_gas: max_priority_fee_per_gas.map(Uint256::from_u128),
+                transaction_type: None,
+            })
+        };
+
+        let attr = |res: &Response, key| -> u128 {
+            res.events[0]
+                .attributes
+                .iter()
+                .find(|attr| attr.key == key)
+                .unwrap()
+                .value
+                .parse()
+                .unwrap()
+        };
+
+        // only the gas used is charged, the rest of the gas limit is refunded
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            add(Some(7), None, None),
+        )
+        .unwrap();
+        let fee = attr(&res, "gas_used") * 7;
+        assert_eq!(attr(&res, "gas_price"), 7);
+        assert_eq!(balance(&deps, sender), 1_000_000_000 - fee);
+        assert_eq!(balance(&deps, Address::ZERO), fee);
+
+        // without a base fee, the priority fee is paid
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            add(None, Some(100), Some(3)),
+        )
+        .unwrap();
+        let fee = fee + attr(&res, "gas_used") * 3;
+        assert_eq!(attr(&res, "gas_price"), 3);
+        assert_eq!(balance(&deps, sender), 1_000_000_000 - fee);
+        assert_eq!(balance(&deps, Address::ZERO), fee);
+
+        // the whole gas limit must be affordable
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            add(Some(10_000), None, None),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InvalidTransaction(InvalidTransaction::LackOfFundForMaxFee { .. })
+        ));
+        assert_eq!(balance(&deps, sender), 1_000_000_000 - fee);
+    }
+
+    /// Instantiates the contract with a gas target of 50,000 and `base_fee`, and funds the EVM
+    /// account of the returned sender.
+    fn instantiate_with_base_fee(
+        base_fee: u128,
+    ) -> (
+        OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        Env,
+        MessageInfo,
+    ) {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            InstantiateMsg {
+                eth_token: "denom".to_owned(),
+                chain_id: CHAIN_ID,
+                coinbase: None,
+                prevrandao: None,
+                gas_ratio: None,
+                sdk_gas_limit: Some(100_000),
+                fee_collector: None,
+                base_fee: Some(Uint256::from(base_fee)),
+                state_trie: None,
+            },
+        )
+        .unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(
+                &info.sender,
+                &[Coin::new(1_000_000_000_000_000_u128, "denom")],
+            ),
+            ExecuteMsg::Lock,
+        )
+        .unwrap();
+
+        (deps, env, info)
+    }
+
+    #[test]
+    fn base_fee() {
+        let (mut deps, env, info) = instantiate_with_base_fee(1_000_000_000);
+
+        let at = |height| {
+            let mut env = env.clone();
+            env.block.height += height;
+            env
+        };
+
+        let transfer = |gas_price: u128| {
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Call(Addr(address!("0x000000000000000000000000000000000000dead"))),
+                value: None,
+                input: None,
+                nonce: None,
+                chain_id: None,
+                gas: Some(21_000),
+                gas_price: Some(Uint256::from(gas_price)),
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            })
+        };
+
+        let base_fee = |deps: &OwnedDeps<_, _, _>, env| -> Uint256 {
+            cosmwasm_std::from_json(query(deps.as_ref(), env, QueryMsg::BaseFee {}).unwrap())
+                .unwrap()
+        };
+
+        // 63_000 gas used, 13_000 above the target
+        for _ in 0..3 {
+            execute(deps.as_mut(), at(0), info.clone(), transfer(2_000_000_000)).unwrap();
+        }
+
+        assert_eq!(base_fee(&deps, at(0)), Uint256::from(1_000_000_000_u128));
+        assert_eq!(base_fee(&deps, at(1)), Uint256::from(1_032_500_000_u128));
+        // no EVM transactions at the next height
+        assert_eq!(base_fee(&deps, at(2)), Uint256::from(903_437_500_u128));
+
+        let err = execute(deps.as_mut(), at(2), info.clone(), transfer(1)).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InvalidTransaction(InvalidTransaction::GasPriceLessThanBasefee)
+        ));
+
+        // 21_000 gas used, 29_000 below the target
+        execute(deps.as_mut(), at(2), info, transfer(2_000_000_000)).unwrap();
+
+        let res: FeeHistoryResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                at(2),
+                QueryMsg::FeeHistory {
+                    block_count: 3,
+                    newest_block: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            res,
+            FeeHistoryResponse {
+                oldest_block: env.block.height,
+                base_fee_per_gas: vec![
+                    Uint256::from(1_000_000_000_u128),
+                    Uint256::from(1_032_500_000_u128),
+                    Uint256::from(903_437_500_u128),
+                    Uint256::from(837_938_282_u128),
+                ],
+                gas_used_ratio: vec![Decimal::percent(63), Decimal::zero(), Decimal::percent(21)],
+            }
+        );
+    }
+
+    #[test]
+    fn fee_history_after_empty_blocks() {
+        let (mut deps, env, info) = instantiate_with_base_fee(1_000_000_000);
+
+        // 63_000 gas used, 13_000 above the target
+        for _ in 0..3 {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                ExecuteMsg::Transaction(Tx {
+                    to: TxKind::Call(Addr(address!("0x000000000000000000000000000000000000dead"))),
+                    value: None,
+                    input: None,
+                    nonce: None,
+                    chain_id: None,
+                    gas: Some(21_000),
+                    gas_price: Some(Uint256::from(2_000_000_000_u128)),
+                    max_fee_per_gas: None,
+                    max_priority_fee_per_gas: None,
+                    transaction_type: None,
+                }),
+            )
+            .unwrap();
+        }
+
+        // the history starts two blocks after the last block with EVM transactions
+        let mut newest = env.clone();
+        newest.block.height += 3;
+
+        let res: FeeHistoryResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                newest.clone(),
+                QueryMsg::FeeHistory {
+                    block_count: 2,
+                    newest_block: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let storage = deps.as_ref().storage;
+        let config = storage.read::<ConfigStore>(()).unwrap().unwrap();
+        let base_fee = |height| {
+            Uint256::from_be_bytes(
+                fee::base_fee(storage, &config, height)
+                    .unwrap()
+                    .to_be_bytes(),
+            )
+        };
+
+        assert_eq!(res.oldest_block, env.block.height + 2);
+        assert_eq!(
+            res.base_fee_per_gas,
+            (env.block.height + 2..=env.block.height + 4)
+                .map(base_fee)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(res.base_fee_per_gas[0], Uint256::from(903_437_500_u128));
+    }
+
+    #[test]
+    fn basefee_opcode() {
+        let (deps, env, info) = instantiate_with_base_fee(1_000_000_000);
+        let sender = evm_address(&deps.api.addr_canonicalize(info.sender.as_str()).unwrap());
+
+        let call = |gas_price: Option<u128>| -> CallResponse {
+            cosmwasm_std::from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::Call {
+                        tx: Tx {
+                            to: TxKind::Create,
+                            value: None,
+                            // BASEFEE PUSH0 MSTORE PUSH1 0x20 PUSH0 RETURN
+                            input: Some(hex!("485f5260205ff3").into()),
+                            nonce: None,
+                            chain_id: None,
+                            gas: Some(100_000),
+                            gas_price: gas_price.map(Uint256::from),
+                            max_fee_per_gas: None,
+                            max_priority_fee_per_gas: None,
+                            transaction_type: None,
+                        },
+                        from: Some(Addr(sender)),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        let res = call(Some(2_000_000_000));
+        assert_eq!(U256::from_be_slice(&res.output), U256::from(1_000_000_000));
+
+        // simulations without a gas price run without a base fee
+        let res = call(None);
+        assert_eq!(U256::from_be_slice(&res.output), U256::ZERO);
+    }
+
+    #[test]
+    fn receipts() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+        let sender = evm_address(&deps.api.addr_canonicalize(info.sender.as_str()).unwrap());
+
+        // add(2)
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                value: None,
+                input: Some(
+                    hex!(
+                        "fc5842bd0000000000000000000000000000000000000000000000000000000000000002"
+                    )
+                    .into(),
+                ),
+                nonce: None,
+                chain_id: None,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            }),
+        )
+        .unwrap();
+
+        let hash: B256 = res.attributes[0].value.parse().unwrap();
+        let gas_used: u64 = res.events[0]
+            .attributes
+            .iter()
+            .find(|attr| attr.key == "gas_used")
+            .unwrap()
+            .value
+            .parse()
+            .unwrap();
+
+        let receipt = |hash: H256| -> Option<TransactionReceipt> {
+            cosmwasm_std::from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::TransactionReceipt { hash },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(receipt(H256(B256::ZERO)), None);
+
+        let receipt = receipt(H256(hash)).unwrap();
+
+        assert_eq!(receipt.transaction_hash, H256(hash));
+        assert_eq!(receipt.block_height, env.block.height);
+        // the counter was deployed in the same block
+        assert_eq!(receipt.transaction_index, 1);
+        assert_eq!(receipt.from, Addr(sender));
+        assert_eq!(receipt.to, Some(Addr(COUNTER_ADDRESS)));
+        assert_eq!(receipt.status, 1);
+        assert_eq!(receipt.gas_used, gas_used);
+        assert!(receipt.cumulative_gas_used > gas_used);
+        assert_eq!(receipt.contract_address, None);
+        // `Added(uint256)` is emitted for every increment
+        assert_eq!(receipt.logs.len(), 2);
+        assert!(
+            receipt
+                .logs
+                .iter()
+                .all(|log| log.address == Addr(COUNTER_ADDRESS))
+        );
+    }
+
+    /// Calls `add(n)` on the counter.
+    fn add(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, env: Env, n: u8) {
+        let info = message_info(&deps.api.addr_make(""), &[]);
+
+        let mut input = hex!("fc5842bd").to_vec();
+        input.extend(B256::with_last_byte(n));
+
+        execute(
+            deps.as_mut(),
+            env,
+            info,
+            ExecuteMsg::Transaction(Tx {
+                to: TxKind::Call(Addr(COUNTER_ADDRESS)),
+                value: None,
+                input: Some(input.into()),
+                nonce: None,
+                chain_id: None,
+                gas: None,
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                transaction_type: None,
+            }),
+        )
+        .unwrap();
+    }
+
+    /// Deploys the counter and emits 2 logs in the current block and 3 in the next. Returns the
+    /// next block.
+    fn emit_logs() -> (OwnedDeps<MockStorage, MockApi, MockQuerier>, Env) {
+        let (mut deps, mut env) = deploy_counter();
+
+        add(&mut deps, env.clone(), 2);
+        env.block.height += 1;
+        add(&mut deps, env.clone(), 3);
+
+        (deps, env)
+    }
+
+    fn query_logs(
+        deps: Deps,
+        env: &Env,
+        filter: LogFilter,
+        start_after: Option<LogCursor>,
+        limit: Option<u32>,
+    ) -> Vec<Log> {
+        cosmwasm_std::from_json(
+            query(
+                deps,
+                env.clone(),
+                QueryMsg::Logs {
+                    filter,
+                    start_after,
+                    limit,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn logs() {
+        let (deps, env) = emit_logs();
+        let first_block = env.block.height - 1;
+
+        let logs = |filter: LogFilter| query_logs(deps.as_ref(), &env, filter, None, None);
+
+        let all = logs(LogFilter {
+            from_block: Some(first_block),
+            ..LogFilter::default()
+        });
+        assert_eq!(
+            all.iter()
+                .map(|log| (log.block_height, log.log_index))
+                .collect::<Vec<_>>(),
+            [
+                (first_block, 0),
+                (first_block, 1),
+                (env.block.height, 0),
+                (env.block.height, 1),
+                (env.block.height, 2),
+            ]
+        );
+        assert!(all[..2].iter().all(|log| log.transaction_index == 1));
+        assert!(all[2..].iter().all(|log| log.transaction_index == 0));
+        assert_ne!(all[0].transaction_hash, all[2].transaction_hash);
+
+        // the block range defaults to the current block
+        assert_eq!(logs(LogFilter::default()), all[2..]);
+
+        let topic = all[0].topics[0];
+        for filter in [
+            LogFilter {
+                address: vec![Addr(COUNTER_ADDRESS), Addr(COUNTER_ADDRESS)],
+                ..LogFilter::default()
+            },
+            LogFilter {
+                topics: vec![Some(vec![H256(B256::ZERO), topic])],
+                ..LogFilter::default()
+            },
+            LogFilter {
+                topics: vec![None; all[0].topics.len()],
+                ..LogFilter::default()
+            },
+        ] {
+            let filter = LogFilter {
+                from_block: Some(first_block),
+                ..filter
+            };
+            assert_eq!(logs(filter), all);
+        }
+
+        for filter in [
+            LogFilter {
+                address: vec![Addr(Address::ZERO)],
+                ..LogFilter::default()
+            },
+            LogFilter {
+                topics: vec![Some(vec![H256(B256::ZERO)])],
+                ..LogFilter::default()
+            },
+            LogFilter {
+                topics: vec![None; all[0].topics.len() + 1],
+                ..LogFilter::default()
+            },
+            LogFilter {
+                from_block: Some(env.block.height + 1),
+                ..LogFilter::default()
+            },
+        ] {
+            assert_eq!(logs(filter), []);
+        }
+    }
+
+    #[test]
+    fn logs_pagination() {
+        let (deps, env) = emit_logs();
+
+        let all = query_logs(
+            deps.as_ref(),
+            &env,
+            LogFilter {
+                from_block: Some(env.block.height - 1),
+                ..LogFilter::default()
+            },
+            None,
+            None,
+        );
+
+        for filter in [
+            LogFilter::default(),
+            LogFilter {
+                address: vec![Addr(COUNTER_ADDRESS)],
+                ..LogFilter::default()
+            },
+            LogFilter {
+                topics: vec![Some(vec![all[0].topics[0]])],
+                ..LogFilter::default()
+            },
+        ] {
+            let filter = LogFilter {
+                from_block: Some(env.block.height - 1),
+                ..filter
+            };
+
+            let mut pages = vec![];
+            let mut start_after = None;
+            loop {
+                let page = query_logs(deps.as_ref(), &env, filter.clone(), start_after, Some(2));
+                let Some(last) = page.last() else {
+                    break;
+                };
+                start_after = Some(LogCursor {
+                    block_height: last.block_height,
+                    log_index: last.log_index,
+                });
+                pages.push(page);
+            }
+
+            assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+            assert_eq!(pages.concat(), all);
+        }
+    }
+
+    #[test]
+    fn block_without_transactions() {
+        let (mut deps, mut env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+        let first = env.block.height;
+
+        // a cosmos block without EVM transactions
+        env.block.height += 1;
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::MigrateLegacyAccount,
+        )
+        .unwrap();
+
+        env.block.height += 1;
+        add(&mut deps, env.clone(), 1);
+
+        let block = |number| -> Option<Block> {
+            cosmwasm_std::from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::BlockByNumber { number },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(block(first + 1), None);
+        assert_eq!(
+            block(env.block.height).unwrap().parent_hash,
+            block(first).unwrap().hash
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        use revm::primitives::alloy_primitives::{Bloom, BloomInput};
+
+        let (deps, env) = emit_logs();
+        let mut parent_env = env.clone();
+        parent_env.block.height -= 1;
+
+        let block = |msg: QueryMsg| -> Option<Block> {
+            cosmwasm_std::from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+
+        let parent = block(QueryMsg::BlockByNumber {
+            number: parent_env.block.height,
+        })
+        .unwrap();
+        let current = block(QueryMsg::BlockByNumber {
+            number: env.block.height,
+        })
+        .unwrap();
+
+        assert_eq!(parent.number, parent_env.block.height);
+        assert_eq!(parent.hash, H256(block_hash(&parent_env.block)));
+        assert_eq!(parent.timestamp, parent_env.block.time.seconds());
+        // the counter was deployed in the same block
+        assert_eq!(parent.transactions.len(), 2);
+
+        assert_eq!(current.hash, H256(block_hash(&env.block)));
+        assert_eq!(current.parent_hash, parent.hash);
+        assert_eq!(current.transactions.len(), 1);
+
+        let logs = query_logs(deps.as_ref(), &env, LogFilter::default(), None, None);
+        let receipt: TransactionReceipt = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::TransactionReceipt {
+                    hash: current.transactions[0],
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(receipt.logs, logs);
+        assert_eq!(current.gas_used, receipt.cumulative_gas_used);
+
+        let bloom = Bloom::from(current.logs_bloom.to_array::<256>().unwrap());
+        assert!(bloom.contains_input(BloomInput::Raw(COUNTER_ADDRESS.as_slice())));
+        assert!(bloom.contains_input(BloomInput::Raw(logs[0].topics[0].0.as_slice())));
+        assert!(!bloom.contains_input(BloomInput::Raw(Address::ZERO.as_slice())));
+
+        assert_eq!(
+            block(QueryMsg::BlockByHash { hash: parent.hash }),
+            Some(parent)
+        );
+        assert_eq!(
+            block(QueryMsg::BlockByNumber {
+                number: env.block.height + 1
+            }),
+            None
+        );
+        assert_eq!(
+            block(QueryMsg::BlockByHash {
+                hash: H256(B256::ZERO)
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn state_trie() {
+        let env = mock_env();
+        let state_root = |deps: Deps| -> Option<B256> {
+            cosmwasm_std::from_json::<Option<H256>>(
+                query(deps, env.clone(), QueryMsg::StateRoot {}).unwrap(),
+            )
+            .unwrap()
+            .map(|root| root.0)
+        };
+
+        assert_eq!(state_root(deploy_counter().0.as_ref()), None);
+
+        let (mut deps, env) = deploy_counter_with_state_trie(Some(true));
+        add(&mut deps, env.clone(), 2);
+
+        let root = state_root(deps.as_ref()).unwrap();
+        assert_ne!(root, trie::EMPTY_ROOT);
+
+        // locking and unlocking leaves an empty account, which is not part of the trie
+        let other = deps.api.addr_make("other");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&other, &[Coin::new(100_u128, "denom")]),
+            ExecuteMsg::Lock,
+        )
+        .unwrap();
+        assert_ne!(state_root(deps.as_ref()), Some(root));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&other, &[]),
+            ExecuteMsg::Unlock(Uint256::from(100_u128)),
+        )
+        .unwrap();
+        assert_eq!(state_root(deps.as_ref()), Some(root));
+
+        let storage_root = deps
+            .as_ref()
+            .storage
+            .read::<StorageRootStore>(COUNTER_ADDRESS)
+            .unwrap()
+            .unwrap();
+        let code_hash = deps
+            .as_ref()
+            .storage
+            .read::<AccountInfoStore>(COUNTER_ADDRESS)
+            .unwrap()
+            .unwrap()
+            .code_hash;
+
+        assert_eq!(
+            trie::get(&deps.storage, root, keccak256(COUNTER_ADDRESS).as_slice()).unwrap(),
+            Some(alloy_rlp::encode(trie::TrieAccount {
+                nonce: 1,
+                balance: U256::ZERO,
+                storage_root,
+                code_hash: code_hash.into(),
+            }))
+        );
+        assert_eq!(
+            trie::get(
+                &deps.storage,
+                storage_root,
+                keccak256(U256::ZERO.to_be_bytes::<32>()).as_slice()
+            )
+            .unwrap(),
+            Some(alloy_rlp::encode(U256::from(3)))
+        );
+    }
+
+    #[test]
+    fn proofs() {
+        let (deps, env) = deploy_counter();
+        assert_eq!(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::Proof {
+                    address: Addr(COUNTER_ADDRESS),
+                    storage_keys: vec![],
+                },
+            ),
+            Err(ContractError::StateTrieDisabled)
+        );
+
+        let (mut deps, env) = deploy_counter_with_state_trie(Some(true));
+        add(&mut deps, env.clone(), 2);
+
+        let root: Option<H256> = cosmwasm_std::from_json(
+            query(deps.as_ref(), env.clone(), QueryMsg::StateRoot {}).unwrap(),
+        )
+        .unwrap();
+        let root = root.unwrap().0;
+
+        let slots = [H256(B256::ZERO), H256(B256::with_last_byte(5))];
+        let proof: ProofResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::Proof {
+                    address: Addr(COUNTER_ADDRESS),
+                    storage_keys: slots.to_vec(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(proof.nonce, 1);
+        assert_eq!(proof.storage_proof[0].value, H256(B256::with_last_byte(3)));
+        assert_eq!(proof.storage_proof[1].value, H256(B256::ZERO));
+
+        // the proof nodes alone are enough to look up the account and its slots
+        let verify = |root: B256, proof: &[HexBinary], key: &[u8]| {
+            let mut storage = MockStorage::new();
+            assert_eq!(keccak256(&proof[0]), root);
+            for node in proof {
+                (&mut storage as &mut dyn Storage)
+                    .write::<TrieNodeStore>(keccak256(node), &node.to_vec());
+            }
+            trie::get(&storage, root, keccak256(key).as_slice()).unwrap()
+        };
+
+        assert_eq!(
+            verify(root, &proof.account_proof, COUNTER_ADDRESS.as_slice()),
+            Some(alloy_rlp::encode(trie::TrieAccount {
+                nonce: proof.nonce,
+                balance: U256::from_be_bytes(proof.balance.to_be_bytes()),
+                storage_root: proof.storage_hash.0,
+                code_hash: proof.code_hash.0,
+            }))
+        );
+        assert_eq!(
+            verify(
+                proof.storage_hash.0,
+                &proof.storage_proof[0].proof,
+                slots[0].0.as_slice()
+            ),
+            Some(alloy_rlp::encode(U256::from(3)))
+        );
+        assert_eq!(
+            verify(
+                proof.storage_hash.0,
+                &proof.storage_proof[1].proof,
+                slots[1].0.as_slice()
+            ),
+            None
+        );
+    }
+
+    /// A transaction to `to` with `value` and `input`, with defaults for everything else.
+    fn tx(to: TxKind, value: u128, input: Vec<u8>) -> ExecuteMsg {
+        ExecuteMsg::Transaction(Tx {
+            to,
+            value: Some(Uint256::from(value)),
+            input: Some(input.into()),
+            nonce: None,
+            chain_id: None,
+            gas: None,
+            gas_price: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            transaction_type: None,
+        })
+    }
+
+    /// Initcode returning `runtime` as the code of the contract.
+    fn initcode(runtime: &[u8]) -> Vec<u8> {
+        let len = u8::try_from(runtime.len()).unwrap();
+        [
+            &[0x60, len, 0x60, 0x0a, 0x5f, 0x39, 0x60, len, 0x5f, 0xf3],
+            runtime,
+        ]
+        .concat()
+    }
+
+    /// Runtime code that calls `target` with the value and calldata it was called with, then
+    /// stops, or reverts if `revert` is set.
+    fn forwarder(target: Address, revert: bool) -> Vec<u8> {
+        [
+            // calldatacopy(0, 0, calldatasize())
+            &hex!("365f5f37")[..],
+            // call(gas(), target, callvalue(), 0, calldatasize(), 0, 0)
+            &hex!("5f5f365f3473"),
+            target.as_slice(),
+            &hex!("5af150"),
+            if revert { &hex!("5f5ffd") } else { &hex!("00") },
+        ]
+        .concat()
+    }
+
+    /// Runtime code that forwards calls to `target` like [`forwarder`], except that calls from
+    /// `target` are recorded as a log of their calldata.
+    fn recorder(target: Address) -> Vec<u8> {
+        let forward = forwarder(target, false);
+        let jumpdest = u8::try_from(forward.len() + 26).unwrap();
+
+        [
+            // jumpi(jumpdest, eq(caller(), target))
+            &hex!("3373")[..],
+            target.as_slice(),
+            &[0x14, 0x60, jumpdest, 0x57],
+            &forward,
+            // calldatacopy(0, 0, calldatasize()) log0(0, calldatasize())
+            &hex!("5b365f5f37365fa000"),
+        ]
+        .concat()
+    }
+
+    fn wasm_execute_input(contract: &str, msg: &[u8], reply: bool) -> Vec<u8> {
+        [
+            &abi::selector("execute(string,bytes,bool)")[..],
+            &abi::encode(&[
+                abi::Token::String(contract.to_owned()),
+                abi::Token::Bytes(msg.to_vec()),
+                abi::Token::Bool(reply),
+            ]),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn wasm_execute() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+        let sender = evm_address(&deps.api.addr_canonicalize(info.sender.as_str()).unwrap());
+        let contract = deps.api.addr_make("contract").to_string();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&info.sender, &[Coin::new(1_000_u128, "denom")]),
+            ExecuteMsg::Lock,
+        )
+        .unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            tx(
+                TxKind::Call(Addr(WASM_ADDRESS)),
+                100,
+                wasm_execute_input(&contract, br#"{"ping":{}}"#, false),
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            res.messages,
+            [SubMsg::reply_never(WasmMsg::Execute {
+                contract_addr: contract.clone(),
+                msg: br#"{"ping":{}}"#.into(),
+                funds: vec![Coin::new(100_u128, "denom")],
+            })]
+        );
+        let account = deps
+            .as_ref()
+            .storage
+            .read::<AccountInfoStore>(WASM_ADDRESS)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (U256::from_be_bytes(account.balance), account.nonce),
+            (U256::ZERO, 1)
+        );
+
+        // executions are numbered by the nonce of the precompile, and only those awaiting a reply
+        // are recorded
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            tx(
+                TxKind::Call(Addr(WASM_ADDRESS)),
+                0,
+                wasm_execute_input(&contract, b"{}", true),
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            res.messages,
+            [SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: contract.clone(),
+                    msg: b"{}".into(),
+                    funds: vec![],
+                },
+                1
+            )]
+        );
+        assert_eq!(
+            deps.as_ref().storage.read::<WasmReplyStore>(0).unwrap(),
+            None
+        );
+        assert_eq!(
+            deps.as_ref().storage.read::<WasmReplyStore>(1).unwrap(),
+            Some((sender, U256::ZERO))
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            info,
+            tx(
+                TxKind::Call(Addr(WASM_ADDRESS)),
+                0,
+                wasm_execute_input(&contract, b"{}", true)[..68].to_vec(),
+            ),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            &err,
+            ContractError::Revert {
+                reason: RevertReason::Error { message },
+                ..
+            } if message == "invalid arguments"
+        ));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn wasm_reply() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+        let sender = evm_address(&deps.api.addr_canonicalize(info.sender.as_str()).unwrap());
+        let contract = deps.api.addr_make("contract").to_string();
+
+        let nonce = deps
+            .as_ref()
+            .storage
+            .read::<AccountInfoStore>(sender)
+            .unwrap()
+            .unwrap()
+            .nonce;
+        let caller = sender.create(nonce);
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            tx(TxKind::Create, 0, initcode(&recorder(WASM_ADDRESS))),
+        )
+        .unwrap();
+
+        for _ in 0..2 {
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                tx(
+                    TxKind::Call(Addr(caller)),
+                    0,
+                    wasm_execute_input(&contract, b"{}", true),
+                ),
+            )
+            .unwrap();
+            assert_eq!(res.messages.len(), 1);
+        }
+
+        let callback = |deps: &mut OwnedDeps<_, _, _>, id, result| {
+            let res = reply(
+                deps.as_mut(),
+                env.clone(),
+                Reply {
+                    id,
+                    payload: Binary::default(),
+                    gas_used: 0,
+                    result,
+                },
+            )?;
+
+            Ok::<_, ContractError>(
+                res.events
+                    .into_iter()
+                    .filter(|event| event.ty == "log")
+                    .map(|event| event.attributes)
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        let log = |id, success, data: &[u8]| {
+            vec![
+                cosmwasm_std::Attribute::new("address", caller.to_string()),
+                cosmwasm_std::Attribute::new(
+                    "data",
+                    format!(
+                        "0x{}",
+                        hex::encode(precompiles::wasm_reply_input(id, success, data.to_vec()))
+                    ),
+                ),
+            ]
+        };
+
+        assert_eq!(
+            callback(
+                &mut deps,
+                0,
+                SubMsgResult::Ok(SubMsgResponse {
+                    events: vec![],
+                    data: Some(b"data".into()),
+                    msg_responses: vec![],
+                }),
+            )
+            .unwrap(),
+            [log(0, true, b"data")]
+        );
+        assert_eq!(
+            callback(&mut deps, 1, SubMsgResult::Err("failed".to_owned())).unwrap(),
+            [log(1, false, b"failed")]
+        );
+
+        // every reply is delivered once
+        assert_eq!(
+            callback(&mut deps, 1, SubMsgResult::Err("failed".to_owned())).unwrap_err(),
+            ContractError::UnknownReply { id: 1 }
+        );
+
+        // the callbacks are transactions from the precompile, and take the next ids
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            tx(
+                TxKind::Call(Addr(caller)),
+                0,
+                wasm_execute_input(&contract, b"{}", true),
+            ),
+        )
+        .unwrap();
+        assert_eq!(res.messages[0].id, 4);
+    }
+
+    #[test]
+    fn wasm_reply_refund() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+        let sender = evm_address(&deps.api.addr_canonicalize(info.sender.as_str()).unwrap());
+        let contract = deps.api.addr_make("contract").to_string();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&info.sender, &[Coin::new(1_000_u128, "denom")]),
+            ExecuteMsg::Lock,
+        )
+        .unwrap();
+
+        let nonce = deps
+            .as_ref()
+            .storage
+            .read::<AccountInfoStore>(sender)
+            .unwrap()
+            .unwrap()
+            .nonce;
+        let caller = sender.create(nonce);
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            tx(TxKind::Create, 0, initcode(&recorder(WASM_ADDRESS))),
+        )
+        .unwrap();
+
+        // the caller forwards the value to the precompile, which burns it
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            tx(
+                TxKind::Call(Addr(caller)),
+                100,
+                wasm_execute_input(&contract, b"{}", true),
+            ),
+        )
+        .unwrap();
+        let id = res.messages[0].id;
+
+        let balance = |deps: &OwnedDeps<_, _, _>, address| {
+            deps.as_ref()
+                .storage
+                .read::<AccountInfoStore>(address)
+                .unwrap()
+                .map(|account| U256::from_be_bytes(account.balance))
+        };
+        assert_eq!(balance(&deps, caller), Some(U256::ZERO));
+        assert_eq!(balance(&deps, WASM_ADDRESS), Some(U256::ZERO));
+
+        reply(
+            deps.as_mut(),
+            env,
+            Reply {
+                id,
+                payload: Binary::default(),
+                gas_used: 0,
+                result: SubMsgResult::Err("failed".to_owned()),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(balance(&deps, caller), Some(U256::from(100)));
+        assert_eq!(balance(&deps, WASM_ADDRESS), Some(U256::ZERO));
+    }
+
+    /// Simulates a call to the function `signature` of the precompile at `address` with `args`.
+    fn precompile_call(
+        deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        env: &Env,
+        address: Address,
+        signature: &str,
+        args: &[abi::Token],
+    ) -> CallResponse {
+        let ExecuteMsg::Transaction(tx) = tx(
+            TxKind::Call(Addr(address)),
+            0,
+            [&abi::selector(signature)[..], &abi::encode(args)].concat(),
+        ) else {
+            unreachable!()
+        };
+
+        cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::Call { tx, from: None },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn wasm_query() {
+        let (mut deps, env) = deploy_counter();
+        let contract = deps.api.addr_make("contract").to_string();
+
+        let oracle = contract.clone();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, msg } if *contract_addr == oracle => {
+                assert_eq!(msg.as_slice(), br#"{"price":{}}"#);
+                SystemResult::Ok(ContractResult::Ok(br#"{"price":"1.5"}"#.into()))
+            }
+            WasmQuery::Raw { contract_addr, key } if *contract_addr == oracle => {
+                SystemResult::Ok(ContractResult::Ok(
+                    to_json_binary(&Binary::from(if key.as_slice() == b"price" {
+                        &b"1.5"[..]
+                    } else {
+                        &[]
+                    }))
+                    .unwrap(),
+                ))
+            }
+            _ => SystemResult::Ok(ContractResult::Err("no such contract".to_owned())),
+        });
+
+        let wasm_query = |signature, contract: &str, msg: &[u8]| {
+            precompile_call(
+                &deps,
+                &env,
+                WASM_ADDRESS,
+                signature,
+                &[
+                    abi::Token::String(contract.to_owned()),
+                    abi::Token::Bytes(msg.to_vec()),
+                ],
+            )
+        };
+
+        let res = wasm_query("query(string,bytes)", &contract, br#"{"price":{}}"#);
+        assert_eq!(res.revert_reason, None);
+        assert_eq!(
+            res.output.as_slice(),
+            abi::encode(&[abi::Token::Bytes(br#"{"price":"1.5"}"#.to_vec())])
+        );
+
+        let res = wasm_query("queryRaw(string,bytes)", &contract, b"price");
+        assert_eq!(
+            res.output.as_slice(),
+            abi::encode(&[abi::Token::Bytes(b"1.5".to_vec())])
+        );
+
+        let res = wasm_query("queryRaw(string,bytes)", &contract, b"missing");
+        assert_eq!(
+            res.output.as_slice(),
+            abi::encode(&[abi::Token::Bytes(vec![])])
+        );
+
+        let res = wasm_query("query(string,bytes)", "unknown", b"{}");
+        assert_eq!(
+            res.revert_reason,
+            Some(RevertReason::Error {
+                message: "no such contract".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn bank_query() {
+        let (mut deps, env) = deploy_counter();
+        let account = deps.api.addr_make("account").to_string();
+        let other = deps.api.addr_make("other").to_string();
+
+        deps.querier
+            .bank
+            .update_balance(&account, vec![Coin::new(500_u128, "denom")]);
+        deps.querier.bank.update_balance(
+            &other,
+            vec![Coin::new(250_u128, "denom"), Coin::new(7_u128, "other")],
+        );
+
+        let uint = |value: u64| abi::encode(&[abi::Token::Uint(U256::from(value))]);
+        let string = |value: &str| abi::Token::String(value.to_owned());
+
+        let res = precompile_call(
+            &deps,
+            &env,
+            BANK_ADDRESS,
+            "balance(string,string)",
+            &[string(&account), string("denom")],
+        );
+        assert_eq!(res.revert_reason, None);
+        assert_eq!(res.output.as_slice(), uint(500));
+
+        let res = precompile_call(
+            &deps,
+            &env,
+            BANK_ADDRESS,
+            "balance(string,string)",
+            &[string(&account), string("other")],
+        );
+        assert_eq!(res.output.as_slice(), uint(0));
+
+        let res = precompile_call(
+            &deps,
+            &env,
+            BANK_ADDRESS,
+            "supply(string)",
+            &[string("denom")],
+        );
+        assert_eq!(res.output.as_slice(), uint(750));
+
+        let res = precompile_call(
+            &deps,
+            &env,
+            BANK_ADDRESS,
+            "balance(string,string)",
+            &[string(&account)],
+        );
+        assert_eq!(
+            res.revert_reason,
+            Some(RevertReason::Error {
+                message: "invalid arguments".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn addr_conversion() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(
+            &deps.api.addr_make("account"),
+            &[Coin::new(1_u128, "denom")],
+        );
+        let sender = evm_address(&deps.api.addr_canonicalize(info.sender.as_str()).unwrap());
+
+        let string = |value: &str| abi::Token::String(value.to_owned());
+
+        let res = precompile_call(
+            &deps,
+            &env,
+            ADDR_ADDRESS,
+            "toEvmAddress(string)",
+            &[string(info.sender.as_str())],
+        );
+        assert_eq!(
+            res.output.as_slice(),
+            abi::encode(&[abi::Token::Address(sender)])
+        );
+
+        // the cosmos address is only known once the account has used the contract
+        let res = precompile_call(
+            &deps,
+            &env,
+            ADDR_ADDRESS,
+            "toCosmosAddress(address)",
+            &[abi::Token::Address(sender)],
+        );
+        assert_eq!(res.output.as_slice(), abi::encode(&[string("")]));
+
+        execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Lock).unwrap();
+
+        let res = precompile_call(
+            &deps,
+            &env,
+            ADDR_ADDRESS,
+            "toCosmosAddress(address)",
+            &[abi::Token::Address(sender)],
+        );
+        assert_eq!(
+            res.output.as_slice(),
+            abi::encode(&[string(info.sender.as_str())])
+        );
+
+        let res = precompile_call(
+            &deps,
+            &env,
+            ADDR_ADDRESS,
+            "toEvmAddress(string)",
+            &[string("invalid")],
+        );
+        assert!(matches!(
+            res.revert_reason,
+            Some(RevertReason::Error { .. })
+        ));
+    }
+
+    #[test]
+    fn bank_send() {
+        let (mut deps, env) = deploy_counter();
+        let info = message_info(&deps.api.addr_make(""), &[]);
+        let sender = evm_address(&deps.api.addr_canonicalize(info.sender.as_str()).unwrap());
+        let recipient = deps.api.addr_make("recipient").to_string();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&info.sender, &[Coin::new(1_000_u128, "denom")]),
+            ExecuteMsg::Lock,
+        )
+        .unwrap();
+
+        let balance = |deps: &OwnedDeps<_, _, _>, address| {
+            U256::from_be_bytes(
+                deps.as_ref()
+                    .storage
+                    .read::<AccountInfoStore>(address)
+                    .unwrap()
+                    .unwrap_or_default()
+                    .balance,
+            )
+        };
+
+        let send = [
+            &abi::selector("send(string)")[..],
+            &abi::encode(&[abi::Token::String(recipient.clone())]),
+        ]
+        .concat();
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            tx(TxKind::Call(Addr(BANK_ADDRESS)), 100, send.clone()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            res.messages,
+            [SubMsg::reply_never(BankMsg::Send {
+                to_address: recipient,
+                amount: vec![Coin::new(100_u128, "denom")],
+            })]
+        );
+        assert_eq!(balance(&deps, sender), U256::from(900));
+        assert_eq!(balance(&deps, BANK_ADDRESS), U256::ZERO);
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            tx(TxKind::Call(Addr(BANK_ADDRESS)), 0, send.clone()),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            &err,
+            ContractError::Revert {
+                reason: RevertReason::Error { message },
+                ..
+            } if message == "nothing to send"
+        ));
+
+        // a send in a reverted call is dropped, even if the transaction succeeds
+        let nonce = deps
+            .as_ref()
+            .storage
+            .read::<AccountInfoStore>(sender)
+            .unwrap()
+            .unwrap()
+            .nonce;
+        let reverter = sender.create(nonce);
+        let caller = sender.create(nonce + 1);
+
+        for runtime in [forwarder(BANK_ADDRESS, true), forwarder(reverter, false)] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                tx(TxKind::Create, 0, initcode(&runtime)),
+            )
+            .unwrap();
+        }
+
+        let res = execute(
+            deps.as_mut(),
+            env,
+            info,
+            tx(TxKind::Call(Addr(caller)), 100, send),
+        )
+        .unwrap();
+
+        assert_eq!(res.messages, []);
+        assert_eq!(balance(&deps, caller), U256::from(100));
+        assert_eq!(balance(&deps, BANK_ADDRESS), U256::ZERO);
+    }
 }
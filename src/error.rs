@@ -0,0 +1,93 @@
+use cosmwasm_std::{HexBinary, StdError, Uint256};
+use revm::primitives::{Address, EVMError, HaltReason, InvalidHeader, InvalidTransaction};
+use thiserror::Error;
+
+use crate::revert::RevertReason;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error(transparent)]
+    Std(#[from] StdError),
+
+    #[error("execution reverted: {reason}")]
+    Revert {
+        reason: RevertReason,
+        data: HexBinary,
+        gas_used: u64,
+    },
+
+    #[error("out of gas: gas limit {gas_limit}")]
+    OutOfGas { gas_limit: u64 },
+
+    #[error("execution halted: {reason:?}")]
+    Halt { reason: HaltReason, gas_used: u64 },
+
+    #[error("invalid transaction: {0}")]
+    InvalidTransaction(InvalidTransaction),
+
+    #[error("invalid block header: {0}")]
+    InvalidHeader(InvalidHeader),
+
+    #[error("unable to decode transaction: {0}")]
+    InvalidRawTransaction(alloy_rlp::Error),
+
+    #[error("transaction is not replay protected, it must be signed with a chain ID (EIP-155)")]
+    UnprotectedTransaction,
+
+    #[error("{address} is a legacy address, its owner must migrate it before using it")]
+    LegacyAccount { address: Address },
+
+    #[error("invalid transaction signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("invalid nonce: expected {expected}, found {found}")]
+    InvalidNonce { expected: u64, found: u64 },
+
+    #[error("insufficient balance: have {balance}, need {required}")]
+    InsufficientBalance { balance: Uint256, required: Uint256 },
+
+    #[error("no funds provided in {denom}")]
+    MissingFunds { denom: String },
+
+    #[error(transparent)]
+    BadStorage(#[from] StorageError),
+
+    #[error("config not found")]
+    MissingConfig,
+
+    #[error("a chain ID is required to migrate a config without one")]
+    MissingChainId,
+
+    #[error("block {height} is not one of the last 256 blocks")]
+    BlockOutOfRange { height: u64 },
+
+    #[error("the state trie is not enabled")]
+    StateTrieDisabled,
+
+    #[error("no execution awaiting a reply with id {id}")]
+    UnknownReply { id: u64 },
+
+    #[error("evm error: {0}")]
+    Evm(String),
+}
+
+impl From<EVMError<StorageError>> for ContractError {
+    fn from(value: EVMError<StorageError>) -> Self {
+        match value {
+            EVMError::Transaction(err) => Self::InvalidTransaction(err),
+            EVMError::Header(err) => Self::InvalidHeader(err),
+            EVMError::Database(err) => Self::BadStorage(err),
+            EVMError::Custom(err) | EVMError::Precompile(err) => Self::Evm(err),
+        }
+    }
+}
+
+/// A value in contract storage that could not be decoded, either because it is corrupt or because it
+/// was written in a format this version of the contract does not understand.
+#[derive(Error, Debug, PartialEq)]
+#[error("bad storage at 0x{key}: {source}")]
+pub struct StorageError {
+    /// The raw storage key.
+    pub key: HexBinary,
+    pub source: StdError,
+}
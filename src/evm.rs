@@ -1,72 +1,316 @@
-use std::convert::Infallible;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
 
-use cosmwasm_std::Storage;
+use cosmwasm_std::{Api, BlockInfo, Env, QuerierWrapper, Storage};
 use revm::{
-    Database, DatabaseCommit,
-    primitives::{Account, AccountInfo, Address, B256, Bytecode, HashMap, U256},
+    Context, Database, DatabaseCommit, FrameOrResult,
+    interpreter::Gas,
+    primitives::{
+        Account, AccountInfo, Address, B256, Bytecode, EVMError, ExecutionResult, HashMap, Log,
+        U256, alloy_primitives::Bloom, keccak256,
+    },
 };
 
-use crate::state::{
-    AccountInfoStore, AccountStorageStore, ContractsStore, RawAccountInfo, StorageExt,
+use crate::{
+    error::{ContractError, StorageError},
+    fee,
+    msg::{Block, H256, RandomnessSource},
+    precompiles,
+    state::{
+        AccountInfoStore, AccountStorageStore, BlockGasStore, BlockHashStore, BlockNumberStore,
+        BlockStore, Config, ConfigStore, ContractsStore, LatestBlockStore, RawAccountInfo,
+        RawBlockGas, StorageExt,
+    },
+    trie,
 };
 
-pub struct Evm<'a> {
-    pub evm: revm::Evm<'a, (), CwDb<'a>>,
+/// An EVM backed by contract storage, with access to the api and querier of the contract for
+/// precompiles.
+///
+/// `S` is the handle to the storage. Only an EVM over `&mut dyn Storage` can commit state changes,
+/// an EVM over `&dyn Storage` can only be used to simulate transactions.
+pub struct Evm<'a, S: Deref<Target = dyn Storage + 'a> = &'a mut dyn Storage> {
+    pub evm: revm::Evm<'a, (), CwDb<'a, S>>,
+    pub config: Config,
+    /// The base fee of the current block, kept separately from the block env so that it is recorded
+    /// correctly even if a transaction is executed without a base fee.
+    base_fee: U256,
 }
 
-impl<'a> Evm<'a> {
-    #[must_use]
-    pub fn new(storage: &'a mut dyn Storage) -> Self {
-        Self {
+impl<'a, S: Deref<Target = dyn Storage + 'a>> Evm<'a, S> {
+    pub fn new(
+        storage: S,
+        api: &'a dyn Api,
+        querier: QuerierWrapper<'a>,
+        env: &Env,
+    ) -> Result<Self, ContractError> {
+        let config = storage
+            .read::<ConfigStore>(())?
+            .ok_or(ContractError::MissingConfig)?;
+
+        let fee_collector = config.fee_collector;
+        let base_fee = fee::base_fee(&*storage, &config, env.block.height)?;
+
+        Ok(Self {
             evm: revm::Evm::builder()
                 .with_spec_id(revm::primitives::SpecId::LATEST)
-                .with_db(CwDb { storage })
+                .with_db(CwDb {
+                    storage,
+                    api,
+                    querier,
+                    commit_error: None,
+                })
+                .modify_cfg_env(|cfg| cfg.chain_id = config.chain_id)
+                .modify_block_env(|block| {
+                    block.number = U256::from(env.block.height);
+                    block.timestamp = U256::from(env.block.time.seconds());
+                    block.coinbase = config.coinbase;
+                    block.gas_limit = U256::from(config.gas_limit());
+                    block.basefee = base_fee;
+                    block.prevrandao = Some(match config.prevrandao {
+                        RandomnessSource::BlockHash => block_hash(&env.block),
+                        RandomnessSource::Fixed(prevrandao) => prevrandao.0,
+                    });
+                })
+                .append_handler_register_box(Box::new(move |handler| {
+                    handler.post_execution.reward_beneficiary = Arc::new(move |context, gas| {
+                        reward_fee_collector(context, gas, fee_collector)
+                    });
+
+                    let call = handler.execution.call.clone();
+                    handler.execution.call = Arc::new(move |context, inputs| {
+                        if precompiles::is_precompile(inputs.bytecode_address) {
+                            Ok(FrameOrResult::new_call_result(
+                                precompiles::call(context, &inputs)?,
+                                inputs.return_memory_offset.clone(),
+                            ))
+                        } else {
+                            call(context, inputs)
+                        }
+                    });
+                }))
                 .build(),
+            config,
+            base_fee,
+        })
+    }
+}
+
+impl<'a, S: DerefMut<Target = dyn Storage + 'a>> Evm<'a, S> {
+    /// The storage backing this EVM.
+    pub fn storage_mut(&mut self) -> &mut dyn Storage {
+        &mut *self.evm.db_mut().storage
+    }
+
+    /// Executes the transaction currently set on the EVM and commits the resulting state changes.
+    pub fn transact_commit(&mut self) -> Result<ExecutionResult, ContractError> {
+        let result = self.evm.transact_commit()?;
+
+        match self.evm.db_mut().commit_error.take() {
+            Some(err) => Err(err.into()),
+            None => Ok(result),
+        }
+    }
+
+    /// Adds the committed transaction `hash` that used `gas_used` and emitted `logs` to the current
+    /// block, from which the base fee of the next block is derived. Returns the updated block.
+    pub fn record_transaction(
+        &mut self,
+        hash: B256,
+        gas_used: u64,
+        logs: &[Log],
+    ) -> Result<RawBlockGas, ContractError> {
+        let height = self.evm.block().number.saturating_to::<u64>();
+        let timestamp = self.evm.block().timestamp.saturating_to::<u64>();
+        let base_fee = self.base_fee;
+        let storage = &mut *self.evm.db_mut().storage;
+
+        let block = fee::block_gas(storage, height)?;
+
+        let block = RawBlockGas {
+            height,
+            gas_used: block
+                .map_or(0, |block| block.gas_used)
+                .saturating_add(gas_used),
+            transaction_count: block.map_or(0, |block| block.transaction_count) + 1,
+            log_count: block.map_or(0, |block| block.log_count) + logs.len() as u64,
+            base_fee: base_fee.to_be_bytes(),
+        };
+
+        storage.write::<BlockGasStore>(height, &block);
+
+        let mut record = match storage.read::<BlockStore>(height)? {
+            Some(record) => record,
+            None => new_block(storage, height, timestamp)?,
+        };
+
+        let mut bloom = Bloom::from(record.logs_bloom.to_array()?);
+        bloom.extend(logs);
+
+        record.logs_bloom = bloom.to_vec().into();
+        record.gas_used = block.gas_used;
+        record.transactions.push(H256(hash));
+
+        storage.write::<BlockStore>(height, &record);
+
+        if self.config.base_fee_height != height {
+            self.config.base_fee = base_fee;
+            self.config.base_fee_height = height;
+
+            storage.write::<ConfigStore>((), &self.config);
         }
+
+        Ok(block)
     }
 }
 
-pub struct CwDb<'a> {
-    storage: &'a mut dyn Storage,
+/// Credits the fee of a transaction to `fee_collector`.
+///
+/// Unlike on Ethereum, the base fee is not burned and the coinbase is not rewarded: the whole fee goes
+/// to the fee collector, so that all EVM balances stay backed by the native tokens held by this
+/// contract.
+fn reward_fee_collector<DB: Database>(
+    context: &mut Context<(), DB>,
+    gas: &Gas,
+    fee_collector: Address,
+) -> Result<(), EVMError<DB::Error>> {
+    let fee = context.evm.env.effective_gas_price()
+        * U256::from(gas.spent() - gas.refunded().unsigned_abs());
+
+    let fee_collector = context
+        .evm
+        .inner
+        .journaled_state
+        .load_account(fee_collector, &mut context.evm.inner.db)?;
+
+    fee_collector.data.mark_touch();
+    fee_collector.data.info.balance = fee_collector.data.info.balance.saturating_add(fee);
+
+    Ok(())
+}
+
+/// A deterministic identifier for a cosmos block, derived from the keccak256 of the chain ID, height
+/// and time of the block.
+#[must_use]
+pub fn block_hash(block: &BlockInfo) -> B256 {
+    keccak256(
+        [
+            block.chain_id.as_bytes(),
+            &block.height.to_be_bytes(),
+            &block.time.nanos().to_be_bytes(),
+        ]
+        .concat(),
+    )
+}
+
+/// Creates the record of the block at `height`, before its first EVM transaction.
+///
+/// Only blocks with EVM transactions are recorded, so the parent of the block is the last recorded
+/// block rather than the block at `height - 1`, keeping the recorded blocks a chain.
+fn new_block(
+    storage: &mut dyn Storage,
+    height: u64,
+    timestamp: u64,
+) -> Result<Block, StorageError> {
+    let hash = recorded_block_hash(storage, height)?;
+    storage.write::<BlockNumberStore>(hash, &height);
+
+    let parent_hash = match storage.read::<LatestBlockStore>(())? {
+        Some(parent) => storage
+            .read::<BlockStore>(parent)?
+            .map_or(B256::ZERO, |parent| parent.hash.0),
+        None => B256::ZERO,
+    };
+    storage.write::<LatestBlockStore>((), &height);
+
+    Ok(Block {
+        number: height,
+        hash: H256(hash),
+        parent_hash: H256(parent_hash),
+        timestamp,
+        logs_bloom: Bloom::ZERO.to_vec().into(),
+        gas_used: 0,
+        transactions: vec![],
+    })
+}
+
+/// The hash of the block at `height` recorded for the `BLOCKHASH` opcode, or zero if there is none.
+fn recorded_block_hash(storage: &dyn Storage, height: u64) -> Result<B256, StorageError> {
+    Ok(storage
+        .read::<BlockHashStore>(height)?
+        .filter(|block_hash| block_hash.height == height)
+        .map(|block_hash| block_hash.hash.into())
+        .unwrap_or_default())
+}
+
+pub struct CwDb<'a, S> {
+    storage: S,
+    api: &'a dyn Api,
+    querier: QuerierWrapper<'a>,
+    /// The first error raised while committing, since [`DatabaseCommit::commit`] cannot return it.
+    commit_error: Option<StorageError>,
 }
 
 pub const ADDRESS_PREFIX: u8 = 0x00;
 pub const CODE_PREFIX: u8 = 0x01;
-pub const BLOCK_HASH_PREFIX: u8 = 0x02;
 
-impl Database for CwDb<'_> {
-    type Error = Infallible;
+impl<'a, S> CwDb<'a, S> {
+    /// The api of the contract, for precompiles that work with cosmos addresses.
+    pub fn api(&self) -> &'a dyn Api {
+        self.api
+    }
+
+    /// The querier of the contract, for precompiles that read the state of the chain.
+    pub fn querier(&self) -> QuerierWrapper<'a> {
+        self.querier
+    }
+}
+
+impl<'a, S: Deref<Target = dyn Storage + 'a>> CwDb<'_, S> {
+    /// The storage of the contract, for precompiles that read state outside of the EVM.
+    pub fn storage(&self) -> &(dyn Storage + 'a) {
+        &*self.storage
+    }
+}
+
+impl<'a, S: Deref<Target = dyn Storage + 'a>> Database for CwDb<'_, S> {
+    type Error = StorageError;
 
     fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
         Ok(self
             .storage
-            .read::<AccountInfoStore>(address)
+            .read::<AccountInfoStore>(address)?
             .map(Into::into))
     }
 
     fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
         Ok(self
             .storage
-            .read::<ContractsStore>(code_hash)
+            .read::<ContractsStore>(code_hash)?
             .unwrap_or_default())
     }
 
     fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
         Ok(self
             .storage
-            .read::<AccountStorageStore>((address, index))
+            .read::<AccountStorageStore>((address, index))?
             .unwrap_or_default())
     }
 
-    fn block_hash(&mut self, _number: u64) -> Result<B256, Self::Error> {
-        todo!()
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        // revm only calls this for the last 256 blocks
+        recorded_block_hash(&*self.storage, number)
     }
 }
 
-impl DatabaseCommit for CwDb<'_> {
+impl<'a, S: DerefMut<Target = dyn Storage + 'a>> DatabaseCommit for CwDb<'_, S> {
     fn commit(&mut self, changes: HashMap<Address, Account>) {
         for (address, mut account) in changes {
-            if let Some(code) = account.info.code.take() {
+            if let Some(code) = account.info.code.take()
+                && !code.is_empty()
+            {
                 self.storage
                     .write::<ContractsStore>(account.info.code_hash, &code);
             }
@@ -76,14 +320,27 @@ impl DatabaseCommit for CwDb<'_> {
                     .write::<AccountStorageStore>((address, *slot), &value.present_value);
             }
 
-            self.storage.write::<AccountInfoStore>(
-                address,
-                &RawAccountInfo::new(
-                    account.info.balance,
-                    account.info.nonce,
-                    account.info.code_hash,
-                ),
+            let info = RawAccountInfo::new(
+                account.info.balance,
+                account.info.nonce,
+                account.info.code_hash,
             );
+
+            self.storage.write::<AccountInfoStore>(address, &info);
+
+            if account.is_touched()
+                && self.commit_error.is_none()
+                && let Err(err) = trie::update_account(
+                    &mut *self.storage,
+                    address,
+                    &info,
+                    account
+                        .changed_storage_slots()
+                        .map(|(slot, value)| (*slot, value.present_value)),
+                )
+            {
+                self.commit_error = Some(err);
+            }
         }
     }
 }
@@ -0,0 +1,157 @@
+use cosmwasm_std::Storage;
+use revm::primitives::U256;
+
+use crate::{
+    error::StorageError,
+    state::{BlockGasStore, Config, FEE_HISTORY, RawBlockGas, StorageExt},
+};
+
+/// The ratio of the gas limit to the gas target of a block.
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+
+/// The bound on the change of the base fee from one block to the next, as a fraction of the base
+/// fee.
+pub const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+
+/// The base fee of the block following a block with `base_fee` and `gas_used`, as specified by
+/// EIP-1559.
+#[must_use]
+pub fn next_base_fee(base_fee: U256, gas_used: u64, gas_limit: u64) -> U256 {
+    let gas_target = gas_limit / ELASTICITY_MULTIPLIER;
+
+    if gas_target == 0 {
+        return base_fee;
+    }
+
+    // the gas limit is enforced per transaction, so a block may use more than it
+    let gas_used = gas_used.min(gas_limit);
+
+    let delta = |gas_delta: u64| {
+        base_fee * U256::from(gas_delta)
+            / U256::from(gas_target)
+            / U256::from(BASE_FEE_MAX_CHANGE_DENOMINATOR)
+    };
+
+    if gas_used > gas_target {
+        base_fee.saturating_add(delta(gas_used - gas_target).max(U256::from(1)))
+    } else {
+        base_fee - delta(gas_target - gas_used)
+    }
+}
+
+/// The base fee of the block at `height`.
+///
+/// Only the base fee of the last block that executed an EVM transaction is stored, in [`Config`];
+/// the base fee of any later block is derived from it, treating the blocks in between as empty.
+pub fn base_fee(storage: &dyn Storage, config: &Config, height: u64) -> Result<U256, StorageError> {
+    let Some(blocks) = height
+        .checked_sub(config.base_fee_height)
+        .filter(|n| *n > 0)
+    else {
+        return Ok(config.base_fee);
+    };
+
+    let gas_used = block_gas(storage, config.base_fee_height)?.map_or(0, |block| block.gas_used);
+
+    Ok(skip_blocks(
+        next_base_fee(config.base_fee, gas_used, config.gas_limit()),
+        blocks - 1,
+        config.gas_limit(),
+    ))
+}
+
+/// The base fee and gas used of every block from `oldest` to `newest`, and the base fee of the
+/// block after `newest`.
+///
+/// Blocks before the last [`FEE_HISTORY`] blocks that executed an EVM transaction are reported with
+/// a base fee of zero.
+pub fn fee_history(
+    storage: &dyn Storage,
+    config: &Config,
+    oldest: u64,
+    newest: u64,
+) -> Result<(Vec<U256>, Vec<u64>), StorageError> {
+    let gas_limit = config.gas_limit();
+
+    // the base fee and gas used of the block before `oldest`
+    let mut parent = None;
+
+    for height in (oldest.saturating_sub(FEE_HISTORY)..oldest).rev() {
+        if let Some(block) = known_block(storage, config, height)? {
+            parent = Some(if height == oldest - 1 {
+                block
+            } else {
+                // the blocks after the known block are empty, as in `base_fee`
+                (
+                    skip_blocks(
+                        next_base_fee(block.0, block.1, gas_limit),
+                        oldest - 2 - height,
+                        gas_limit,
+                    ),
+                    0,
+                )
+            });
+            break;
+        }
+    }
+
+    let mut base_fees = vec![];
+    let mut gas_used = vec![];
+
+    for height in oldest..=newest.saturating_add(1) {
+        let block = match known_block(storage, config, height)? {
+            Some(block) => block,
+            None => (
+                parent.map_or(U256::ZERO, |(base_fee, gas_used)| {
+                    next_base_fee(base_fee, gas_used, gas_limit)
+                }),
+                0,
+            ),
+        };
+
+        base_fees.push(block.0);
+        if height <= newest {
+            gas_used.push(block.1);
+        }
+
+        parent = Some(block);
+    }
+
+    Ok((base_fees, gas_used))
+}
+
+/// The gas used by the block at `height`, if it executed an EVM transaction.
+pub fn block_gas(storage: &dyn Storage, height: u64) -> Result<Option<RawBlockGas>, StorageError> {
+    Ok(storage
+        .read::<BlockGasStore>(height)?
+        .filter(|block| block.height == height))
+}
+
+/// The base fee and gas used of the block at `height`, if they are stored.
+fn known_block(
+    storage: &dyn Storage,
+    config: &Config,
+    height: u64,
+) -> Result<Option<(U256, u64)>, StorageError> {
+    Ok(match block_gas(storage, height)? {
+        Some(block) => Some((U256::from_be_bytes(block.base_fee), block.gas_used)),
+        None if height == config.base_fee_height => Some((config.base_fee, 0)),
+        None => None,
+    })
+}
+
+/// Applies `blocks` empty blocks to `base_fee`.
+fn skip_blocks(mut base_fee: U256, blocks: u64, gas_limit: u64) -> U256 {
+    for _ in 0..blocks {
+        let next = next_base_fee(base_fee, 0, gas_limit);
+
+        // the base fee stops decreasing eventually, so there is no need to go through every block
+        if next == base_fee {
+            break;
+        }
+
+        base_fee = next;
+    }
+
+    base_fee
+}
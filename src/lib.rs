@@ -3,9 +3,16 @@
 #![warn(clippy::pedantic, clippy::panic)]
 #![allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
 
+pub mod abi;
 pub mod contract;
 pub mod error;
+pub mod fee;
+pub mod logs;
 pub mod msg;
+pub mod precompiles;
+pub mod revert;
 pub mod state;
+pub mod trie;
+pub mod tx;
 
 pub mod evm;
@@ -0,0 +1,136 @@
+use cosmwasm_std::Storage;
+
+use crate::{
+    error::StorageError,
+    msg::{Log, LogFilter},
+    state::{LogStore, LogsByAddressStore, LogsByTopicStore, RawLogId, StorageExt},
+};
+
+/// The number of logs returned by a logs query if no limit is given.
+pub const DEFAULT_LOGS_LIMIT: u32 = 100;
+
+/// The maximum number of logs returned by a logs query.
+pub const MAX_LOGS_LIMIT: u32 = 1000;
+
+/// Stores `logs` along with the indexes used to filter them.
+pub fn store_logs(storage: &mut dyn Storage, logs: &[Log]) {
+    for log in logs {
+        let id = RawLogId {
+            height: log.block_height,
+            log_index: log.log_index,
+        };
+
+        storage.write::<LogStore>((id.height, id.log_index), log);
+        storage.write::<LogsByAddressStore>((log.address.0, id.height, id.log_index), &id);
+
+        if let Some(topic) = log.topics.first() {
+            storage.write::<LogsByTopicStore>((topic.0, id.height, id.log_index), &id);
+        }
+    }
+}
+
+/// The first `limit` logs matching `filter` from block `from` to block `to` (inclusive), ordered by
+/// block height and log index, starting after the log at `start_after`.
+///
+/// The block range of `filter` is ignored, the caller resolves it to `from` and `to`.
+pub fn filter_logs(
+    storage: &dyn Storage,
+    filter: &LogFilter,
+    from: u64,
+    to: u64,
+    start_after: Option<(u64, u64)>,
+    limit: usize,
+) -> Result<Vec<Log>, StorageError> {
+    let start = start_after
+        .map_or((from, 0), |(height, log_index)| {
+            (height, log_index.saturating_add(1))
+        })
+        .max((from, 0));
+    let end = (to.saturating_add(1), 0);
+
+    if start >= end {
+        return Ok(vec![]);
+    }
+
+    let mut logs = vec![];
+
+    // scan the most selective index available; every source yields its logs in order, so `limit`
+    // matches from each of them are enough
+    if !filter.address.is_empty() {
+        for address in &filter.address {
+            let ids = storage.scan::<LogsByAddressStore>(
+                (address.0, start.0, start.1),
+                (address.0, end.0, end.1),
+            );
+            collect_indexed(storage, filter, ids, limit, &mut logs)?;
+        }
+    } else if let Some(Some(topics)) = filter.topics.first()
+        && !topics.is_empty()
+    {
+        for topic in topics {
+            let ids = storage
+                .scan::<LogsByTopicStore>((topic.0, start.0, start.1), (topic.0, end.0, end.1));
+            collect_indexed(storage, filter, ids, limit, &mut logs)?;
+        }
+    } else {
+        for log in storage.scan::<LogStore>(start, end) {
+            if logs.len() == limit {
+                break;
+            }
+
+            let log = log?;
+            if matches(filter, &log) {
+                logs.push(log);
+            }
+        }
+    }
+
+    logs.sort_by_key(|log| (log.block_height, log.log_index));
+    logs.dedup_by_key(|log| (log.block_height, log.log_index));
+    logs.truncate(limit);
+
+    Ok(logs)
+}
+
+/// Appends the first `limit` logs referenced by `ids` that match `filter` to `logs`.
+fn collect_indexed(
+    storage: &dyn Storage,
+    filter: &LogFilter,
+    ids: impl Iterator<Item = Result<RawLogId, StorageError>>,
+    limit: usize,
+    logs: &mut Vec<Log>,
+) -> Result<(), StorageError> {
+    let mut found = 0;
+
+    for id in ids {
+        if found == limit {
+            break;
+        }
+
+        let id = id?;
+        if let Some(log) = storage.read::<LogStore>((id.height, id.log_index))?
+            && matches(filter, &log)
+        {
+            logs.push(log);
+            found += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `log` matches the addresses and topics of `filter`, following the semantics of
+/// `eth_getLogs`.
+fn matches(filter: &LogFilter, log: &Log) -> bool {
+    (filter.address.is_empty() || filter.address.contains(&log.address))
+        && filter.topics.len() <= log.topics.len()
+        && filter
+            .topics
+            .iter()
+            .zip(&log.topics)
+            .all(|(allowed, topic)| {
+                allowed
+                    .as_ref()
+                    .is_none_or(|allowed| allowed.is_empty() || allowed.contains(topic))
+            })
+}
@@ -1,24 +1,367 @@
+use std::num::NonZeroU64;
+
 use cosmwasm_schema::{
-    cw_serde,
+    QueryResponses, cw_serde,
     serde::{Deserialize, Serialize},
 };
-use cosmwasm_std::{HexBinary, Uint256};
-use revm::primitives::Address;
+use cosmwasm_std::{Decimal, HexBinary, Uint256};
+use revm::primitives::{Address, B256};
+
+use crate::revert::RevertReason;
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub eth_token: String,
+    /// The EVM chain ID. Transactions for any other chain are rejected.
+    pub chain_id: u64,
+    /// The block beneficiary, as returned by the `COINBASE` opcode. Defaults to the zero address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<Addr>,
+    /// The source of the value returned by the `PREVRANDAO` opcode. Defaults to
+    /// [`RandomnessSource::BlockHash`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prevrandao: Option<RandomnessSource>,
+    /// The amount of EVM gas equivalent to one unit of SDK gas. Defaults to
+    /// [`DEFAULT_GAS_RATIO`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_ratio: Option<NonZeroU64>,
+    /// The SDK gas budget of a single transaction. Together with `gas_ratio`, this determines the
+    /// maximum EVM gas limit of a transaction. Defaults to [`DEFAULT_SDK_GAS_LIMIT`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk_gas_limit: Option<u64>,
+    /// The address credited with the gas fees of every transaction. Defaults to the coinbase.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_collector: Option<Addr>,
+    /// The EIP-1559 base fee of the current block, in wei. Defaults to zero.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_fee: Option<Uint256>,
+    /// Whether to maintain a Merkle-Patricia trie of the EVM state, so that its root can be
+    /// queried and proven against. Defaults to false, and cannot be changed later.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_trie: Option<bool>,
 }
 
+pub const DEFAULT_GAS_RATIO: NonZeroU64 = NonZeroU64::new(1).expect("1 is non-zero; qed;");
+
+pub const DEFAULT_SDK_GAS_LIMIT: u64 = 30_000_000;
+
+/// The source of the value returned by the `PREVRANDAO` opcode.
+///
+/// Note that neither of these are a secure source of randomness.
 #[cw_serde]
+pub enum RandomnessSource {
+    /// The identifier of the current cosmos block, derived from its chain ID, height and time.
+    BlockHash,
+    /// A fixed value.
+    Fixed(H256),
+}
+
+#[cw_serde]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     Transaction(Tx),
+    /// A signed, RLP encoded legacy, EIP-2930 or EIP-1559 transaction, as sent to
+    /// `eth_sendRawTransaction`. The transaction is executed as the address that signed it, not the
+    /// sender of this message.
+    RawTransaction(HexBinary),
     Lock,
     Unlock(Uint256),
+    /// Move the balance of the sender's account under the legacy address derivation scheme to the
+    /// sender's current account.
+    ///
+    /// Accounts that existed when the contract was migrated from the legacy scheme are moved
+    /// automatically on the first message of their owner, so this is only needed for value sent to
+    /// a legacy address since.
+    MigrateLegacyAccount,
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    /// The balance, nonce and code hash of an account.
+    #[returns(AccountInfoResponse)]
+    AccountInfo { address: Addr },
+    /// The balance of an account, in wei.
+    #[returns(Uint256)]
+    Balance { address: Addr },
+    /// The nonce of an account.
+    #[returns(u64)]
+    Nonce { address: Addr },
+    /// The code deployed at an address. Empty if the account has no code.
+    #[returns(HexBinary)]
+    Code { address: Addr },
+    /// The code with the given code hash. Empty if no such code is stored.
+    #[returns(HexBinary)]
+    CodeByHash { hash: H256 },
+    /// The EVM address of a cosmos account.
+    #[returns(Addr)]
+    EvmAddress { address: String },
+    /// The cosmos account that an EVM address belongs to, if it has interacted with this contract.
+    #[returns(Option<cosmwasm_std::Addr>)]
+    CosmosAddress { address: Addr },
+    /// The value of a single storage slot of an account.
+    #[returns(H256)]
+    Storage { address: Addr, slot: H256 },
+    /// Execute a transaction without committing any state changes, in the manner of `eth_call`.
+    ///
+    /// If `from` is not set, the transaction is sent from the zero address.
+    #[returns(CallResponse)]
+    Call {
+        tx: Tx,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from: Option<Addr>,
+    },
+    /// Estimate the gas required to execute a transaction, in the manner of `eth_estimateGas`.
+    ///
+    /// If `from` is not set, the transaction is sent from the zero address.
+    #[returns(EstimateGasResponse)]
+    EstimateGas {
+        tx: Tx,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from: Option<Addr>,
+    },
+    /// The receipt of a committed transaction, by its hash. `None` for transactions that reverted
+    /// or halted, as they are never committed.
+    ///
+    /// The hash of a [`ExecuteMsg::RawTransaction`] is the keccak256 of its encoding, as on
+    /// ethereum. See [`crate::tx::unsigned_tx_hash`] for the hash of a [`ExecuteMsg::Transaction`].
+    #[returns(Option<TransactionReceipt>)]
+    TransactionReceipt { hash: H256 },
+    /// The block at height `number`, in the manner of `eth_getBlockByNumber`. Only blocks that
+    /// executed an EVM transaction are known.
+    #[returns(Option<Block>)]
+    BlockByNumber { number: u64 },
+    /// The block with hash `hash`, in the manner of `eth_getBlockByHash`. Only blocks that executed
+    /// an EVM transaction are known.
+    #[returns(Option<Block>)]
+    BlockByHash { hash: H256 },
+    /// The logs matching `filter`, in the manner of `eth_getLogs`, ordered by block height and log
+    /// index.
+    ///
+    /// At most `limit` logs are returned, 100 by default and 1000 at most. To get the next page,
+    /// pass the position of the last returned log as `start_after`.
+    #[returns(Vec<Log>)]
+    Logs {
+        filter: LogFilter,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start_after: Option<LogCursor>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<u32>,
+    },
+    /// The root of the Merkle-Patricia trie of the EVM state, as in the `stateRoot` of an ethereum
+    /// block header. Only set if the contract was instantiated with
+    /// [`InstantiateMsg::state_trie`].
+    #[returns(Option<H256>)]
+    StateRoot {},
+    /// The account at `address` and the values of its `storage_keys`, with their Merkle proofs
+    /// against [`QueryMsg::StateRoot`], in the manner of `eth_getProof`. Fails if the state trie is
+    /// not enabled.
+    #[returns(ProofResponse)]
+    Proof {
+        address: Addr,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        storage_keys: Vec<H256>,
+    },
+    /// The EIP-1559 base fee of the current block, in wei, as returned by the `BASEFEE` opcode.
+    #[returns(Uint256)]
+    BaseFee {},
+    /// The base fee and gas usage of recent blocks, in the manner of `eth_feeHistory`.
+    ///
+    /// If `newest_block` is not set, the history ends at the current block. At most 1024 blocks
+    /// are returned.
+    #[returns(FeeHistoryResponse)]
+    FeeHistory {
+        block_count: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        newest_block: Option<u64>,
+    },
+}
+
+/// Migrates a deployment whose config predates the EVM chain ID, when the config was only the
+/// denom, to the current config. The other fields are as in [`InstantiateMsg`]. Deployments that
+/// already have a current config are left unchanged.
+#[cw_serde]
+#[derive(Default)]
+pub struct MigrateMsg {
+    /// The EVM chain ID. Required if the config predates it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prevrandao: Option<RandomnessSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_ratio: Option<NonZeroU64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk_gas_limit: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_collector: Option<Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_fee: Option<Uint256>,
 }
 
 #[cw_serde]
-pub struct MigrateMsg {}
+pub enum SudoMsg {
+    /// Set the hash of a block, as returned by the `BLOCKHASH` opcode. This overrides the hash
+    /// derived from the chain ID, height and time of the block.
+    ///
+    /// Only the hashes of the last 256 blocks are kept, so `height` must be the current block or one
+    /// of the 255 before it.
+    SetBlockHash { height: u64, hash: H256 },
+}
+
+#[cw_serde]
+pub struct CallResponse {
+    /// The data returned by the call, or the deployed code for a contract creation. Empty if the
+    /// call reverted.
+    pub output: HexBinary,
+    pub gas_used: u64,
+    /// The revert data, if the call reverted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert: Option<HexBinary>,
+    /// The decoded revert data, if the call reverted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<RevertReason>,
+}
+
+#[cw_serde]
+pub struct EstimateGasResponse {
+    /// The lowest gas limit with which the transaction succeeds. Not set if the transaction fails
+    /// regardless of the gas limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas: Option<u64>,
+    /// The revert data, if the transaction reverts regardless of the gas limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert: Option<HexBinary>,
+    /// The decoded revert data, if the transaction reverts regardless of the gas limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<RevertReason>,
+}
+
+#[cw_serde]
+pub struct FeeHistoryResponse {
+    /// The height of the first block in the history.
+    pub oldest_block: u64,
+    /// The base fee of each block in the history, in wei, followed by the base fee of the block
+    /// after the newest one.
+    pub base_fee_per_gas: Vec<Uint256>,
+    /// The ratio of the gas used by each block in the history to the gas limit.
+    pub gas_used_ratio: Vec<Decimal>,
+}
+
+#[cw_serde]
+pub struct ProofResponse {
+    pub address: Addr,
+    pub balance: Uint256,
+    pub nonce: u64,
+    pub code_hash: H256,
+    /// The root of the storage trie of the account.
+    pub storage_hash: H256,
+    /// The RLP encoded trie nodes on the path from the state root to the account.
+    pub account_proof: Vec<HexBinary>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+#[cw_serde]
+pub struct StorageProof {
+    pub key: H256,
+    pub value: H256,
+    /// The RLP encoded trie nodes on the path from the storage root to the slot.
+    pub proof: Vec<HexBinary>,
+}
+
+/// A synthetic EVM block for a cosmos block that executed EVM transactions.
+#[cw_serde]
+pub struct Block {
+    /// The height of the cosmos block.
+    pub number: u64,
+    /// The hash of the block, as returned by the `BLOCKHASH` opcode.
+    pub hash: H256,
+    /// The hash of the previous block that executed EVM transactions, which is not necessarily the
+    /// block at `number - 1`, or zero if there is none.
+    pub parent_hash: H256,
+    /// The time of the block, in seconds since the unix epoch.
+    pub timestamp: u64,
+    /// The 2048 bit bloom filter of the addresses and topics of all logs in the block.
+    pub logs_bloom: HexBinary,
+    pub gas_used: u64,
+    /// The hashes of the EVM transactions in the block, in order of execution.
+    pub transactions: Vec<H256>,
+}
+
+/// The receipt of a committed transaction, in the manner of `eth_getTransactionReceipt`.
+///
+/// A transaction that reverts or halts fails the cosmos transaction carrying it, which discards all
+/// of its state, so only successful transactions have a receipt.
+#[cw_serde]
+pub struct TransactionReceipt {
+    pub transaction_hash: H256,
+    /// The height of the block the transaction was committed in.
+    pub block_height: u64,
+    /// The index of the transaction in its block.
+    pub transaction_index: u64,
+    pub from: Addr,
+    /// The destination of the transaction. Not set for contract creations.
+    pub to: Option<Addr>,
+    /// Always `1` (success), since only committed transactions have a receipt.
+    pub status: u8,
+    pub gas_used: u64,
+    /// The gas used by the transaction and all transactions before it in its block.
+    pub cumulative_gas_used: u64,
+    /// The price per gas paid by the transaction, in wei.
+    pub effective_gas_price: Uint256,
+    /// The address of the created contract, for contract creations.
+    pub contract_address: Option<Addr>,
+    pub logs: Vec<Log>,
+}
+
+#[cw_serde]
+pub struct Log {
+    /// The address of the contract that emitted the log.
+    pub address: Addr,
+    pub topics: Vec<H256>,
+    pub data: HexBinary,
+    pub block_height: u64,
+    pub transaction_hash: H256,
+    /// The index of the transaction that emitted the log in its block.
+    pub transaction_index: u64,
+    /// The index of the log in its block.
+    pub log_index: u64,
+}
+
+/// A filter for logs, as passed to `eth_getLogs`.
+#[cw_serde]
+#[derive(Default)]
+pub struct LogFilter {
+    /// The first block to search. Defaults to the current block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_block: Option<u64>,
+    /// The last block to search. Defaults to the current block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_block: Option<u64>,
+    /// The addresses that may have emitted the logs. Logs emitted by any address match if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub address: Vec<Addr>,
+    /// The allowed values of the topic at each position. A position without values matches any
+    /// topic, but logs with fewer topics than there are positions never match.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub topics: Vec<Option<Vec<H256>>>,
+}
+
+/// The position of a log, used for pagination.
+#[cw_serde]
+pub struct LogCursor {
+    pub block_height: u64,
+    pub log_index: u64,
+}
+
+#[cw_serde]
+pub struct AccountInfoResponse {
+    /// The balance of the account, in wei.
+    pub balance: Uint256,
+    pub nonce: u64,
+    pub code_hash: H256,
+}
 
 /// Represents _all_ transaction requests to/from RPC.
 #[cw_serde]
@@ -31,12 +374,25 @@ pub struct Tx {
     /// Transaction data.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub input: Option<HexBinary>,
-    /// The nonce of the transaction.
+    /// The nonce of the transaction. If not set, the current nonce of the sender is used.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub nonce: Option<u64>,
-    /// The chain ID for the transaction.
+    /// The chain ID for the transaction. If set, it must match the chain ID of this contract.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub chain_id: Option<u64>,
+    /// The gas limit of the transaction. If not set, the maximum gas limit is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas: Option<u64>,
+    /// The gas price of a legacy transaction, in wei. Defaults to zero.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_price: Option<Uint256>,
+    /// The maximum total fee per gas of an EIP-1559 transaction, in wei. Takes precedence over
+    /// `gas_price`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas: Option<Uint256>,
+    /// The maximum priority fee per gas of an EIP-1559 transaction, in wei.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas: Option<Uint256>,
     #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
     #[doc(alias = "tx_type")]
     pub transaction_type: Option<u8>,
@@ -85,3 +441,37 @@ impl cosmwasm_schema::schemars::JsonSchema for Addr {
         )
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "cosmwasm_schema::serde")]
+pub struct H256(pub B256);
+
+impl cosmwasm_schema::schemars::JsonSchema for H256 {
+    fn schema_name() -> String {
+        "H256".to_owned()
+    }
+
+    fn json_schema(
+        _g: &mut cosmwasm_schema::schemars::r#gen::SchemaGenerator,
+    ) -> cosmwasm_schema::schemars::schema::Schema {
+        cosmwasm_schema::schemars::schema::Schema::Object(
+            cosmwasm_schema::schemars::schema::SchemaObject {
+                metadata: Some(Box::new(cosmwasm_schema::schemars::schema::Metadata {
+                    description: Some("A 32 byte hash or word".to_owned()),
+                    ..Default::default()
+                })),
+                instance_type: Some(cosmwasm_schema::schemars::schema::SingleOrVec::Single(
+                    Box::new(cosmwasm_schema::schemars::schema::InstanceType::String),
+                )),
+                string: Some(Box::new(
+                    cosmwasm_schema::schemars::schema::StringValidation {
+                        max_length: Some(66),
+                        min_length: Some(66),
+                        pattern: Some("^0x[0-9a-fA-F]{64}$".to_owned()),
+                    },
+                )),
+                ..Default::default()
+            },
+        )
+    }
+}
@@ -0,0 +1,687 @@
+//! Precompiles that give EVM contracts access to the cosmos chain.
+//!
+//! Unlike the precompiles of revm, these see the caller and value of the call, so they are
+//! dispatched from the call handler of the EVM rather than from its precompile set.
+//!
+//! # Bank
+//!
+//! The precompile at [`BANK_ADDRESS`] implements
+//!
+//! ```solidity
+//! interface IBank {
+//!     /// Sends `msg.value` to the cosmos address `to`, in the native token.
+//!     function send(string calldata to) external payable;
+//!
+//!     /// The balance of the cosmos address `account` in `denom`.
+//!     function balance(string calldata account, string calldata denom)
+//!         external view returns (uint256);
+//!
+//!     /// The total supply of `denom`.
+//!     function supply(string calldata denom) external view returns (uint256);
+//!
+//!     event Send(address indexed from, string to, uint256 amount);
+//! }
+//! ```
+//!
+//! # Wasm
+//!
+//! The precompile at [`WASM_ADDRESS`] implements
+//!
+//! ```solidity
+//! interface IWasm {
+//!     /// Executes the JSON message `msg` on the wasm contract `contractAddress`, with
+//!     /// `msg.value` as funds in the native token. If `reply` is set, the result is passed to
+//!     /// `IWasmCallback.onWasmReply` of the caller once the execution has completed, and
+//!     /// `msg.value` is refunded to the caller if it failed. Otherwise a failed execution fails
+//!     /// the whole transaction.
+//!     function execute(string calldata contractAddress, bytes calldata msg, bool reply)
+//!         external payable returns (uint64 id);
+//!
+//!     /// The JSON response to the smart query `msg` of the wasm contract `contractAddress`.
+//!     function query(string calldata contractAddress, bytes calldata msg)
+//!         external view returns (bytes memory response);
+//!
+//!     /// The value stored under `key` by the wasm contract `contractAddress`, or nothing if there
+//!     /// is none.
+//!     function queryRaw(string calldata contractAddress, bytes calldata key)
+//!         external view returns (bytes memory value);
+//!
+//!     event Execute(
+//!         address indexed from,
+//!         uint64 indexed id,
+//!         string contractAddress,
+//!         bytes msg,
+//!         uint256 amount,
+//!         bool reply
+//!     );
+//! }
+//!
+//! interface IWasmCallback {
+//!     /// Called by the wasm precompile with the result of the execution `id`. On success, `data`
+//!     /// is the protobuf encoded `MsgExecuteContractResponse`, otherwise it is the error message.
+//!     function onWasmReply(uint64 id, bool success, bytes calldata data) external;
+//! }
+//! ```
+//!
+//! Queries are executed synchronously, and revert with the error of the query if it fails.
+//!
+//! The callback is executed as a transaction from [`WASM_ADDRESS`] that pays no fees. If it fails,
+//! the cosmos transaction that executed the wasm contract fails with it.
+//!
+//! # Addresses
+//!
+//! The precompile at [`ADDR_ADDRESS`] implements
+//!
+//! ```solidity
+//! interface IAddr {
+//!     /// The EVM address of the cosmos account `account`.
+//!     function toEvmAddress(string calldata account) external view returns (address);
+//!
+//!     /// The cosmos address of the EVM address `account`, or an empty string if it is not the
+//!     /// address of a cosmos account that has used this contract.
+//!     function toCosmosAddress(address account) external view returns (string memory);
+//! }
+//! ```
+//!
+//! # Queued messages
+//!
+//! Value sent to a precompile is held by it until the transaction is committed, when it is burned
+//! and paid out of the contract with the queued cosmos message. Every message is recorded as a log,
+//! so messages queued in reverted calls are dropped along with their logs.
+
+use std::ops::Deref;
+
+use cosmwasm_std::{
+    Binary, ContractResult, Empty, QueryRequest, Storage, SystemResult, WasmQuery, from_json,
+    to_json_vec,
+};
+use revm::{
+    Context, Database,
+    interpreter::{CallInputs, CallScheme, Gas, InstructionResult, InterpreterResult},
+    primitives::{Address, B256, Bytes, EVMError, Log, LogData, U256, address, b256, hex},
+};
+
+use crate::{
+    abi::{self, Decoder, Token},
+    contract::evm_address,
+    error::StorageError,
+    evm::CwDb,
+    msg,
+    revert::ERROR_SELECTOR,
+    state::{AddressMappingStore, StorageExt},
+};
+
+/// The address of the bank precompile.
+pub const BANK_ADDRESS: Address = address!("0x0000000000000000000000000000000000000800");
+
+/// The address of the wasm precompile.
+pub const WASM_ADDRESS: Address = address!("0x0000000000000000000000000000000000000801");
+
+/// The address of the address conversion precompile.
+pub const ADDR_ADDRESS: Address = address!("0x0000000000000000000000000000000000000802");
+
+/// The gas cost of `IBank.send`.
+pub const SEND_GAS: u64 = 30_000;
+
+/// The gas cost of `IBank.balance` and `IBank.supply`.
+pub const BANK_QUERY_GAS: u64 = 10_000;
+
+/// The gas cost of `IWasm.execute`, excluding [`EXECUTE_BYTE_GAS`].
+pub const EXECUTE_GAS: u64 = 40_000;
+
+/// The gas cost of every byte of the message of `IWasm.execute`.
+pub const EXECUTE_BYTE_GAS: u64 = 8;
+
+/// The gas cost of `IWasm.query` and `IWasm.queryRaw`, excluding [`QUERY_BYTE_GAS`].
+pub const QUERY_GAS: u64 = 10_000;
+
+/// The gas cost of every byte of the request and response of `IWasm.query` and `IWasm.queryRaw`.
+pub const QUERY_BYTE_GAS: u64 = 3;
+
+/// `send(string)`
+const SEND_SELECTOR: [u8; 4] = hex!("66792ba1");
+
+/// `balance(string,string)`
+const BALANCE_SELECTOR: [u8; 4] = hex!("c87258cd");
+
+/// `supply(string)`
+const SUPPLY_SELECTOR: [u8; 4] = hex!("6923a1fd");
+
+/// `Send(address,string,uint256)`
+const SEND_EVENT: B256 = b256!("6e7e202f5d2584c5a224bf3f81ea66365022c0a6134e189f04e2c4cf5b016405");
+
+/// `execute(string,bytes,bool)`
+const EXECUTE_SELECTOR: [u8; 4] = hex!("51801f82");
+
+/// `Execute(address,uint64,string,bytes,uint256,bool)`
+const EXECUTE_EVENT: B256 =
+    b256!("2fe86a060fe0663a6609fa9977cdbb7c03df9de23f2b42e3ea77526a4e2fc3b9");
+
+/// `query(string,bytes)`
+const QUERY_SELECTOR: [u8; 4] = hex!("06d81d29");
+
+/// `queryRaw(string,bytes)`
+const QUERY_RAW_SELECTOR: [u8; 4] = hex!("506e35d0");
+
+/// The gas cost of `IAddr.toEvmAddress` and `IAddr.toCosmosAddress`.
+pub const ADDR_GAS: u64 = 3_000;
+
+/// `toEvmAddress(string)`
+const TO_EVM_ADDRESS_SELECTOR: [u8; 4] = hex!("7804044f");
+
+/// `toCosmosAddress(address)`
+const TO_COSMOS_ADDRESS_SELECTOR: [u8; 4] = hex!("9f6dd28d");
+
+/// `onWasmReply(uint64,bool,bytes)`
+const ON_WASM_REPLY_SELECTOR: [u8; 4] = hex!("a713e6d4");
+
+/// Whether calls to `address` are handled by [`call`].
+#[must_use]
+pub fn is_precompile(address: Address) -> bool {
+    matches!(address, BANK_ADDRESS | WASM_ADDRESS | ADDR_ADDRESS)
+}
+
+/// A failed call to a precompile.
+enum Failure {
+    Revert(String),
+    OutOfGas,
+}
+
+/// Executes a call to the precompile at `inputs.bytecode_address`.
+pub fn call<'a, S: Deref<Target = dyn Storage + 'a>>(
+    context: &mut Context<(), CwDb<'_, S>>,
+    inputs: &CallInputs,
+) -> Result<InterpreterResult, EVMError<StorageError>> {
+    let mut gas = Gas::new(inputs.gas_limit);
+    let checkpoint = context.evm.journaled_state.checkpoint();
+
+    let outcome = match inputs.bytecode_address {
+        BANK_ADDRESS => bank(context, inputs, &mut gas)?,
+        WASM_ADDRESS => wasm(context, inputs, &mut gas)?,
+        ADDR_ADDRESS => addr(context, inputs, &mut gas)?,
+        address => Err(Failure::Revert(format!("no precompile at {address}"))),
+    };
+
+    let (result, output) = match outcome {
+        Ok(output) => (InstructionResult::Return, output),
+        Err(Failure::Revert(message)) => (
+            InstructionResult::Revert,
+            [&ERROR_SELECTOR[..], &abi::encode(&[Token::String(message)])]
+                .concat()
+                .into(),
+        ),
+        Err(Failure::OutOfGas) => {
+            gas.spend_all();
+            (InstructionResult::PrecompileOOG, Bytes::new())
+        }
+    };
+
+    if result.is_ok() {
+        context.evm.journaled_state.checkpoint_commit();
+    } else {
+        context.evm.journaled_state.checkpoint_revert(checkpoint);
+    }
+
+    Ok(InterpreterResult {
+        result,
+        output,
+        gas,
+    })
+}
+
+fn bank<'a, S: Deref<Target = dyn Storage + 'a>>(
+    context: &mut Context<(), CwDb<'_, S>>,
+    inputs: &CallInputs,
+    gas: &mut Gas,
+) -> Result<Result<Bytes, Failure>, EVMError<StorageError>> {
+    let Some((selector, args)) = inputs.input.split_first_chunk::<4>() else {
+        return Ok(Err(Failure::Revert("missing function selector".to_owned())));
+    };
+
+    match *selector {
+        SEND_SELECTOR => {
+            if !gas.record_cost(SEND_GAS) {
+                return Ok(Err(Failure::OutOfGas));
+            }
+
+            send(context, inputs, args)
+        }
+        BALANCE_SELECTOR | SUPPLY_SELECTOR => {
+            if !gas.record_cost(BANK_QUERY_GAS) {
+                return Ok(Err(Failure::OutOfGas));
+            }
+
+            Ok(bank_query(
+                context,
+                inputs,
+                *selector == SUPPLY_SELECTOR,
+                args,
+            ))
+        }
+        selector => Ok(Err(Failure::Revert(format!(
+            "unknown function selector 0x{}",
+            hex::encode(selector)
+        )))),
+    }
+}
+
+/// `IBank.send`
+fn send<DB: Database>(
+    context: &mut Context<(), DB>,
+    inputs: &CallInputs,
+    args: &[u8],
+) -> Result<Result<Bytes, Failure>, EVMError<DB::Error>> {
+    if let Err(failure) = direct_call(inputs, "send") {
+        return Ok(Err(failure));
+    }
+
+    let Some(to) = Decoder::new(args).string(0) else {
+        return Ok(Err(Failure::Revert("invalid arguments".to_owned())));
+    };
+
+    let amount = inputs.call_value();
+
+    if amount.is_zero() {
+        return Ok(Err(Failure::Revert("nothing to send".to_owned())));
+    }
+
+    if let Err(failure) = escrow(context, inputs, BANK_ADDRESS)? {
+        return Ok(Err(failure));
+    }
+
+    context.evm.journaled_state.log(Log {
+        address: BANK_ADDRESS,
+        data: LogData::new_unchecked(
+            vec![SEND_EVENT, inputs.caller.into_word()],
+            abi::encode(&[Token::String(to), Token::Uint(amount)]).into(),
+        ),
+    });
+
+    Ok(Ok(Bytes::new()))
+}
+
+fn wasm<'a, S: Deref<Target = dyn Storage + 'a>>(
+    context: &mut Context<(), CwDb<'_, S>>,
+    inputs: &CallInputs,
+    gas: &mut Gas,
+) -> Result<Result<Bytes, Failure>, EVMError<StorageError>> {
+    let Some((selector, args)) = inputs.input.split_first_chunk::<4>() else {
+        return Ok(Err(Failure::Revert("missing function selector".to_owned())));
+    };
+
+    match *selector {
+        EXECUTE_SELECTOR => {
+            if !gas.record_cost(EXECUTE_GAS) {
+                return Ok(Err(Failure::OutOfGas));
+            }
+
+            execute(context, inputs, args, gas)
+        }
+        QUERY_SELECTOR | QUERY_RAW_SELECTOR => {
+            if !gas.record_cost(QUERY_GAS) {
+                return Ok(Err(Failure::OutOfGas));
+            }
+
+            Ok(query(
+                context,
+                inputs,
+                *selector == QUERY_RAW_SELECTOR,
+                args,
+                gas,
+            ))
+        }
+        selector => Ok(Err(Failure::Revert(format!(
+            "unknown function selector 0x{}",
+            hex::encode(selector)
+        )))),
+    }
+}
+
+/// `IWasm.execute`
+fn execute<DB: Database>(
+    context: &mut Context<(), DB>,
+    inputs: &CallInputs,
+    args: &[u8],
+    gas: &mut Gas,
+) -> Result<Result<Bytes, Failure>, EVMError<DB::Error>> {
+    if let Err(failure) = direct_call(inputs, "execute") {
+        return Ok(Err(failure));
+    }
+
+    let args = Decoder::new(args);
+    let (Some(contract), Some(msg), Some(reply)) = (args.string(0), args.bytes(1), args.bool(2))
+    else {
+        return Ok(Err(Failure::Revert("invalid arguments".to_owned())));
+    };
+
+    if !gas.record_cost(EXECUTE_BYTE_GAS.saturating_mul(msg.len() as u64)) {
+        return Ok(Err(Failure::OutOfGas));
+    }
+
+    let amount = inputs.call_value();
+
+    context
+        .evm
+        .inner
+        .journaled_state
+        .load_account(WASM_ADDRESS, &mut context.evm.inner.db)?;
+
+    if !amount.is_zero()
+        && let Err(failure) = escrow(context, inputs, WASM_ADDRESS)?
+    {
+        return Ok(Err(failure));
+    }
+
+    // the nonce of the precompile counts the executions, so that every execution has a unique id
+    let Some(id) = context.evm.journaled_state.inc_nonce(WASM_ADDRESS) else {
+        return Ok(Err(Failure::Revert("too many executions".to_owned())));
+    };
+    let id = id - 1;
+
+    context.evm.journaled_state.log(Log {
+        address: WASM_ADDRESS,
+        data: LogData::new_unchecked(
+            vec![
+                EXECUTE_EVENT,
+                inputs.caller.into_word(),
+                U256::from(id).into(),
+            ],
+            abi::encode(&[
+                Token::String(contract),
+                Token::Bytes(msg.to_vec()),
+                Token::Uint(amount),
+                Token::Bool(reply),
+            ])
+            .into(),
+        ),
+    });
+
+    Ok(Ok(abi::encode(&[Token::Uint(U256::from(id))]).into()))
+}
+
+/// `IBank.balance`, or `IBank.supply` if `supply` is set.
+fn bank_query<'a, S: Deref<Target = dyn Storage + 'a>>(
+    context: &Context<(), CwDb<'_, S>>,
+    inputs: &CallInputs,
+    supply: bool,
+    args: &[u8],
+) -> Result<Bytes, Failure> {
+    view(inputs)?;
+
+    let args = Decoder::new(args);
+    let querier = context.evm.inner.db.querier();
+
+    let coin = if supply {
+        let Some(denom) = args.string(0) else {
+            return Err(Failure::Revert("invalid arguments".to_owned()));
+        };
+
+        querier.query_supply(denom)
+    } else {
+        let (Some(account), Some(denom)) = (args.string(0), args.string(1)) else {
+            return Err(Failure::Revert("invalid arguments".to_owned()));
+        };
+
+        querier.query_balance(account, denom)
+    }
+    .map_err(|err| Failure::Revert(err.to_string()))?;
+
+    Ok(abi::encode(&[Token::Uint(U256::from(coin.amount.u128()))]).into())
+}
+
+/// `IWasm.query`, or `IWasm.queryRaw` if `raw` is set.
+fn query<'a, S: Deref<Target = dyn Storage + 'a>>(
+    context: &Context<(), CwDb<'_, S>>,
+    inputs: &CallInputs,
+    raw: bool,
+    args: &[u8],
+    gas: &mut Gas,
+) -> Result<Bytes, Failure> {
+    view(inputs)?;
+
+    let args = Decoder::new(args);
+    let (Some(contract_addr), Some(msg)) = (args.string(0), args.bytes(1)) else {
+        return Err(Failure::Revert("invalid arguments".to_owned()));
+    };
+
+    if !gas.record_cost(QUERY_BYTE_GAS.saturating_mul(msg.len() as u64)) {
+        return Err(Failure::OutOfGas);
+    }
+
+    let request = QueryRequest::<Empty>::Wasm(if raw {
+        WasmQuery::Raw {
+            contract_addr,
+            key: msg.into(),
+        }
+    } else {
+        WasmQuery::Smart {
+            contract_addr,
+            msg: msg.into(),
+        }
+    });
+
+    let response = match context
+        .evm
+        .inner
+        .db
+        .querier()
+        .raw_query(&to_json_vec(&request).expect("query request is serializable; qed;"))
+    {
+        SystemResult::Ok(ContractResult::Ok(response)) => response,
+        SystemResult::Ok(ContractResult::Err(err)) => return Err(Failure::Revert(err)),
+        SystemResult::Err(err) => return Err(Failure::Revert(err.to_string())),
+    };
+
+    // the response to a raw query is the JSON encoded value, rather than the value itself
+    let response = if raw {
+        from_json::<Binary>(&response).map_err(|err| Failure::Revert(err.to_string()))?
+    } else {
+        response
+    };
+
+    if !gas.record_cost(QUERY_BYTE_GAS.saturating_mul(response.len() as u64)) {
+        return Err(Failure::OutOfGas);
+    }
+
+    Ok(abi::encode(&[Token::Bytes(response.into())]).into())
+}
+
+fn addr<'a, S: Deref<Target = dyn Storage + 'a>>(
+    context: &Context<(), CwDb<'_, S>>,
+    inputs: &CallInputs,
+    gas: &mut Gas,
+) -> Result<Result<Bytes, Failure>, EVMError<StorageError>> {
+    let Some((selector, args)) = inputs.input.split_first_chunk::<4>() else {
+        return Ok(Err(Failure::Revert("missing function selector".to_owned())));
+    };
+
+    match *selector {
+        TO_EVM_ADDRESS_SELECTOR => {
+            if !gas.record_cost(ADDR_GAS) {
+                return Ok(Err(Failure::OutOfGas));
+            }
+
+            Ok(to_evm_address(context, inputs, args))
+        }
+        TO_COSMOS_ADDRESS_SELECTOR => {
+            if !gas.record_cost(ADDR_GAS) {
+                return Ok(Err(Failure::OutOfGas));
+            }
+
+            to_cosmos_address(context, inputs, args)
+        }
+        selector => Ok(Err(Failure::Revert(format!(
+            "unknown function selector 0x{}",
+            hex::encode(selector)
+        )))),
+    }
+}
+
+/// `IAddr.toEvmAddress`
+fn to_evm_address<'a, S: Deref<Target = dyn Storage + 'a>>(
+    context: &Context<(), CwDb<'_, S>>,
+    inputs: &CallInputs,
+    args: &[u8],
+) -> Result<Bytes, Failure> {
+    view(inputs)?;
+
+    let Some(account) = Decoder::new(args).string(0) else {
+        return Err(Failure::Revert("invalid arguments".to_owned()));
+    };
+
+    let canonical = context
+        .evm
+        .inner
+        .db
+        .api()
+        .addr_canonicalize(&account)
+        .map_err(|err| Failure::Revert(err.to_string()))?;
+
+    Ok(abi::encode(&[Token::Address(evm_address(&canonical))]).into())
+}
+
+/// `IAddr.toCosmosAddress`
+fn to_cosmos_address<'a, S: Deref<Target = dyn Storage + 'a>>(
+    context: &Context<(), CwDb<'_, S>>,
+    inputs: &CallInputs,
+    args: &[u8],
+) -> Result<Result<Bytes, Failure>, EVMError<StorageError>> {
+    if let Err(failure) = view(inputs) {
+        return Ok(Err(failure));
+    }
+
+    let Some(account) = Decoder::new(args).address(0) else {
+        return Ok(Err(Failure::Revert("invalid arguments".to_owned())));
+    };
+
+    let db = &context.evm.inner.db;
+
+    // the EVM address of a cosmos account is a hash of its address, so it can only be converted
+    // back if the account is known
+    let address = match db
+        .storage()
+        .read::<AddressMappingStore>(account)
+        .map_err(EVMError::Database)?
+    {
+        Some(canonical) => match db.api().addr_humanize(&canonical) {
+            Ok(address) => address.into_string(),
+            Err(err) => return Ok(Err(Failure::Revert(err.to_string()))),
+        },
+        None => String::new(),
+    };
+
+    Ok(Ok(abi::encode(&[Token::String(address)]).into()))
+}
+
+/// Fails if value is sent to a function that only reads the state of the chain.
+fn view(inputs: &CallInputs) -> Result<(), Failure> {
+    if !inputs.call_value().is_zero() {
+        return Err(Failure::Revert("queries are not payable".to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Fails unless `function` is called with a plain call outside of a static call, as it moves value
+/// and queues messages.
+fn direct_call(inputs: &CallInputs, function: &str) -> Result<(), Failure> {
+    if inputs.scheme != CallScheme::Call || inputs.is_static {
+        return Err(Failure::Revert(format!(
+            "{function} must be called directly and outside of a static call"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Moves the value of the call from the caller to the precompile at `address`, which holds it until
+/// its message is paid out.
+fn escrow<DB: Database>(
+    context: &mut Context<(), DB>,
+    inputs: &CallInputs,
+    address: Address,
+) -> Result<Result<(), Failure>, EVMError<DB::Error>> {
+    let amount = inputs.call_value();
+
+    if amount > U256::from(u128::MAX) {
+        return Ok(Err(Failure::Revert("amount too large".to_owned())));
+    }
+
+    let inner = &mut context.evm.inner;
+
+    if inner
+        .journaled_state
+        .transfer(&inputs.caller, &address, amount, &mut inner.db)?
+        .is_some()
+    {
+        return Ok(Err(Failure::Revert("insufficient balance".to_owned())));
+    }
+
+    Ok(Ok(()))
+}
+
+/// A cosmos message queued by a precompile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// A `BankMsg::Send` queued by `IBank.send`.
+    BankSend { to: String, amount: U256 },
+    /// A `WasmMsg::Execute` queued by `IWasm.execute`.
+    WasmExecute {
+        id: u64,
+        from: Address,
+        contract: String,
+        msg: Vec<u8>,
+        funds: U256,
+        reply: bool,
+    },
+}
+
+impl Message {
+    /// The precompile holding the value paid out with this message, and that value.
+    #[must_use]
+    pub fn escrow(&self) -> (Address, U256) {
+        match self {
+            Self::BankSend { amount, .. } => (BANK_ADDRESS, *amount),
+            Self::WasmExecute { funds, .. } => (WASM_ADDRESS, *funds),
+        }
+    }
+}
+
+/// The messages queued by precompiles in `logs`, in the order they were queued.
+pub fn messages(logs: &[msg::Log]) -> impl Iterator<Item = Message> {
+    logs.iter().filter_map(|log| {
+        let data = Decoder::new(&log.data);
+
+        match (log.address.0, log.topics.as_slice()) {
+            (BANK_ADDRESS, [event, _]) if event.0 == SEND_EVENT => Some(Message::BankSend {
+                to: data.string(0)?,
+                amount: data.uint(1)?,
+            }),
+            (WASM_ADDRESS, [event, from, id]) if event.0 == EXECUTE_EVENT => {
+                Some(Message::WasmExecute {
+                    id: U256::from_be_bytes(id.0.0).try_into().ok()?,
+                    from: Address::from_word(from.0),
+                    contract: data.string(0)?,
+                    msg: data.bytes(1)?.to_vec(),
+                    funds: data.uint(2)?,
+                    reply: data.bool(3)?,
+                })
+            }
+            _ => None,
+        }
+    })
+}
+
+/// The input of the call to `IWasmCallback.onWasmReply` with the result of the execution `id`.
+#[must_use]
+pub fn wasm_reply_input(id: u64, success: bool, data: Vec<u8>) -> Vec<u8> {
+    [
+        &ON_WASM_REPLY_SELECTOR[..],
+        &abi::encode(&[
+            Token::Uint(U256::from(id)),
+            Token::Bool(success),
+            Token::Bytes(data),
+        ]),
+    ]
+    .concat()
+}
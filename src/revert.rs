@@ -0,0 +1,95 @@
+use std::fmt;
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{HexBinary, Uint256};
+use revm::primitives::{U256, hex};
+
+use crate::abi::Decoder;
+
+/// The selector of `Error(string)`, as used by `revert("...")` and `require(cond, "...")`.
+pub const ERROR_SELECTOR: [u8; 4] = hex!("08c379a0");
+
+/// The selector of `Panic(uint256)`, as used by failing assertions and checked arithmetic.
+const PANIC_SELECTOR: [u8; 4] = hex!("4e487b71");
+
+/// The decoded reason for a revert.
+#[cw_serde]
+pub enum RevertReason {
+    /// `Error(string)`, raised by `revert("...")` and `require(cond, "...")`.
+    Error { message: String },
+    /// `Panic(uint256)`, raised by failing assertions, arithmetic overflow, out of bounds access and
+    /// similar.
+    Panic { code: Uint256, description: String },
+    /// A custom error, or any other revert data starting with a 4 byte selector.
+    Custom {
+        selector: HexBinary,
+        data: HexBinary,
+    },
+    /// Revert data that is too short to contain a selector, such as the empty data of `revert()`.
+    Raw { data: HexBinary },
+}
+
+impl RevertReason {
+    /// Decodes the data returned by a reverted call.
+    #[must_use]
+    pub fn decode(data: &[u8]) -> Self {
+        let Some((selector, args)) = data.split_first_chunk::<4>() else {
+            return Self::Raw {
+                data: data.to_vec().into(),
+            };
+        };
+
+        match *selector {
+            ERROR_SELECTOR => {
+                if let Some(message) = Decoder::new(args).string(0) {
+                    return Self::Error { message };
+                }
+            }
+            PANIC_SELECTOR => {
+                if let Ok(code) = <[u8; 32]>::try_from(args) {
+                    let code = U256::from_be_bytes(code);
+
+                    return Self::Panic {
+                        code: Uint256::from_be_bytes(code.to_be_bytes()),
+                        description: panic_description(code).to_owned(),
+                    };
+                }
+            }
+            _ => {}
+        }
+
+        Self::Custom {
+            selector: selector.to_vec().into(),
+            data: args.to_vec().into(),
+        }
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error { message } => f.write_str(message),
+            Self::Panic { code, description } => write!(f, "panic {code}: {description}"),
+            Self::Custom { selector, data } => write!(f, "custom error 0x{selector}: 0x{data}"),
+            Self::Raw { data } => write!(f, "0x{data}"),
+        }
+    }
+}
+
+/// The meaning of a Solidity panic code, as documented in
+/// <https://docs.soliditylang.org/en/latest/control-structures.html#panic-via-assert-and-error-via-require>.
+fn panic_description(code: U256) -> &'static str {
+    match code.try_into() {
+        Ok(0x00_u8) => "generic compiler inserted panic",
+        Ok(0x01) => "assertion failed",
+        Ok(0x11) => "arithmetic underflow or overflow",
+        Ok(0x12) => "division or modulo by zero",
+        Ok(0x21) => "invalid enum value",
+        Ok(0x22) => "incorrectly encoded storage byte array",
+        Ok(0x31) => "pop on empty array",
+        Ok(0x32) => "array index out of bounds",
+        Ok(0x41) => "out of memory",
+        Ok(0x51) => "call to zero-initialized internal function",
+        _ => "unknown panic code",
+    }
+}
@@ -1,11 +1,41 @@
+use std::num::NonZeroU64;
+
+use crate::{
+    error::StorageError,
+    msg::{
+        Block, DEFAULT_GAS_RATIO, DEFAULT_SDK_GAS_LIMIT, Log, RandomnessSource, TransactionReceipt,
+    },
+};
 use bytemuck::{Pod, Zeroable};
-use cosmwasm_std::Storage;
+use cosmwasm_schema::serde::{Deserialize, Serialize};
+use cosmwasm_std::{CanonicalAddr, Order, StdError, StdResult, Storage, from_json, to_json_vec};
 use revm::primitives::{AccountInfo, Address, B256, Bytecode, U256, hex};
 
 const ACCOUNT_INFO_PREFIX: u8 = 0x0;
 const ACCOUNT_STORAGE_PREFIX: u8 = 0x1;
 const CONTRACTS_PREFIX: u8 = 0x2;
 const CONFIG_PREFIX: u8 = 0x3;
+const ADDRESS_MAPPING_PREFIX: u8 = 0x4;
+const BLOCK_HASH_PREFIX: u8 = 0x5;
+const BLOCK_GAS_PREFIX: u8 = 0x6;
+const RECEIPTS_PREFIX: u8 = 0x7;
+const LOGS_PREFIX: u8 = 0x8;
+const LOGS_BY_ADDRESS_PREFIX: u8 = 0x9;
+const LOGS_BY_TOPIC_PREFIX: u8 = 0xa;
+const BLOCKS_PREFIX: u8 = 0xb;
+const BLOCK_NUMBERS_PREFIX: u8 = 0xc;
+const TRIE_NODES_PREFIX: u8 = 0xd;
+const STORAGE_ROOTS_PREFIX: u8 = 0xe;
+const STATE_ROOT_PREFIX: u8 = 0xf;
+const WASM_REPLIES_PREFIX: u8 = 0x10;
+const LEGACY_ACCOUNTS_PREFIX: u8 = 0x11;
+const LATEST_BLOCK_PREFIX: u8 = 0x12;
+
+/// The number of blocks for which hashes are available to the `BLOCKHASH` opcode.
+pub const BLOCK_HASH_HISTORY: u64 = 256;
+
+/// The number of blocks for which the gas used and base fee are kept.
+pub const FEE_HISTORY: u64 = 1024;
 
 pub trait Store {
     type K;
@@ -15,7 +45,13 @@ pub trait Store {
 
     fn encode(value: &Self::V) -> Vec<u8>;
 
-    fn decode(bz: &[u8]) -> Self::V;
+    fn decode(bz: &[u8]) -> StdResult<Self::V>;
+}
+
+/// Interprets `bz` as a fixed size array, failing if it is not exactly `N` bytes long.
+fn fixed<const N: usize>(bz: &[u8]) -> StdResult<[u8; N]> {
+    bz.try_into()
+        .map_err(|_| StdError::invalid_data_size(N, bz.len()))
 }
 
 #[derive(Clone, Copy, Pod, Zeroable)]
@@ -25,8 +61,54 @@ where
     [u8; N]: Pod + Zeroable,
     [u8; M]: Pod + Zeroable;
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "cosmwasm_schema::serde")]
 pub struct Config {
     pub denom: String,
+    /// The EVM chain ID, as returned by the `CHAINID` opcode.
+    pub chain_id: u64,
+    /// The block beneficiary, as returned by the `COINBASE` opcode.
+    pub coinbase: Address,
+    /// The source of the value returned by the `PREVRANDAO` opcode.
+    pub prevrandao: RandomnessSource,
+    /// The amount of EVM gas equivalent to one unit of SDK gas.
+    #[serde(default = "default_gas_ratio")]
+    pub gas_ratio: NonZeroU64,
+    /// The SDK gas budget of a single transaction.
+    #[serde(default = "default_sdk_gas_limit")]
+    pub sdk_gas_limit: u64,
+    /// The address credited with the gas fees of every transaction.
+    #[serde(default)]
+    pub fee_collector: Address,
+    /// The EIP-1559 base fee of the block at `base_fee_height`, in wei. The base fee of later blocks
+    /// is derived from it.
+    #[serde(default)]
+    pub base_fee: U256,
+    /// The height of the last block that executed an EVM transaction.
+    #[serde(default)]
+    pub base_fee_height: u64,
+}
+
+impl Config {
+    /// The maximum EVM gas limit of a transaction, as returned by the `GASLIMIT` opcode.
+    #[must_use]
+    pub fn gas_limit(&self) -> u64 {
+        self.sdk_gas_limit.saturating_mul(self.gas_ratio.get())
+    }
+
+    /// Converts an amount of EVM gas to SDK gas, rounding up.
+    #[must_use]
+    pub fn sdk_gas(&self, gas: u64) -> u64 {
+        gas.div_ceil(self.gas_ratio.get())
+    }
+}
+
+fn default_gas_ratio() -> NonZeroU64 {
+    DEFAULT_GAS_RATIO
+}
+
+fn default_sdk_gas_limit() -> u64 {
+    DEFAULT_SDK_GAS_LIMIT
 }
 
 pub enum ConfigStore {}
@@ -41,13 +123,33 @@ impl Store for ConfigStore {
     }
 
     fn encode(value: &Self::V) -> Vec<u8> {
-        value.denom.as_bytes().into()
+        to_json_vec(value).expect("config is serializable; qed;")
     }
 
-    fn decode(bz: &[u8]) -> Self::V {
-        Config {
-            denom: String::from_utf8(bz.to_vec()).expect("bad storage"),
-        }
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        from_json(bz)
+    }
+}
+
+/// The config as stored before it was JSON encoded, when it was only the denom. Only read to
+/// migrate it to [`ConfigStore`].
+pub enum LegacyConfigStore {}
+
+impl Store for LegacyConfigStore {
+    type K = ();
+
+    type V = String;
+
+    fn encode_key((): Self::K) -> impl AsRef<[u8]> {
+        [CONFIG_PREFIX]
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        value.as_bytes().to_vec()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        String::from_utf8(bz.to_vec()).map_err(StdError::invalid_utf8)
     }
 }
 
@@ -106,8 +208,55 @@ impl Store for AccountInfoStore {
         bytemuck::must_cast_ref::<Self::V, [u8; 72]>(value).to_vec()
     }
 
-    fn decode(bz: &[u8]) -> Self::V {
-        bytemuck::must_cast::<[u8; 72], Self::V>(bz.try_into().expect("bad storage"))
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<72>(bz).map(bytemuck::must_cast)
+    }
+}
+
+/// All accounts, in ascending order of address.
+pub fn accounts(
+    storage: &dyn Storage,
+) -> impl Iterator<Item = Result<(Address, RawAccountInfo), StorageError>> {
+    storage
+        .range(
+            Some(&[ACCOUNT_INFO_PREFIX]),
+            Some(&[ACCOUNT_INFO_PREFIX + 1]),
+            Order::Ascending,
+        )
+        .map(|(key, raw)| {
+            let account = AccountInfoStore::decode(&raw).map_err(|source| StorageError {
+                key: key.clone().into(),
+                source,
+            })?;
+
+            Ok((Address::from_slice(&key[1..]), account))
+        })
+}
+
+/// The accounts that existed when the contract was migrated from the legacy address derivation
+/// scheme, whose owners have not used the contract since.
+///
+/// The private keys of legacy addresses are public, so these accounts are moved to the current
+/// address of their owner on the owner's first message, and signed transactions from them are
+/// rejected until then.
+pub enum LegacyAccountStore {}
+
+impl Store for LegacyAccountStore {
+    type K = Address;
+
+    type V = ();
+
+    fn encode_key(address: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 21]>(RawKey([LEGACY_ACCOUNTS_PREFIX], address.0.0))
+    }
+
+    fn encode((): &Self::V) -> Vec<u8> {
+        // storage values must not be empty
+        vec![1]
+    }
+
+    fn decode(_: &[u8]) -> StdResult<Self::V> {
+        Ok(())
     }
 }
 
@@ -129,8 +278,8 @@ impl Store for AccountStorageStore {
         value.to_be_bytes::<32>().to_vec()
     }
 
-    fn decode(bz: &[u8]) -> Self::V {
-        U256::from_be_bytes::<32>(bz.try_into().expect("bad storage"))
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<32>(bz).map(U256::from_be_bytes)
     }
 }
 
@@ -146,27 +295,415 @@ impl Store for ContractsStore {
     }
 
     fn encode(value: &Self::V) -> Vec<u8> {
-        value.bytes().into()
+        value.original_bytes().into()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        Ok(Bytecode::new_raw(bz.to_vec().into()))
+    }
+}
+
+/// The reverse mapping of EVM addresses to the canonical addresses of the cosmos accounts they were
+/// derived from.
+pub enum AddressMappingStore {}
+
+impl Store for AddressMappingStore {
+    type K = Address;
+
+    type V = CanonicalAddr;
+
+    fn encode_key(address: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 21]>(RawKey([ADDRESS_MAPPING_PREFIX], address.0.0))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        value.to_vec()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        Ok(bz.to_vec().into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct RawBlockHash {
+    pub height: u64,
+    pub hash: [u8; 32],
+}
+
+/// A ring buffer of the hashes of the last [`BLOCK_HASH_HISTORY`] blocks.
+///
+/// Entries are keyed by `height % BLOCK_HASH_HISTORY`, so the height of a read entry must be checked
+/// against the requested height.
+pub enum BlockHashStore {}
+
+impl Store for BlockHashStore {
+    type K = u64;
+
+    type V = RawBlockHash;
+
+    fn encode_key(height: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 9]>(RawKey(
+            [BLOCK_HASH_PREFIX],
+            (height % BLOCK_HASH_HISTORY).to_be_bytes(),
+        ))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        bytemuck::must_cast_ref::<Self::V, [u8; 40]>(value).to_vec()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<40>(bz).map(bytemuck::must_cast)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct RawBlockGas {
+    pub height: u64,
+    pub gas_used: u64,
+    pub transaction_count: u64,
+    pub log_count: u64,
+    pub base_fee: [u8; 32],
+}
+
+/// A ring buffer of the gas used, transaction and log counts and base fee of the last
+/// [`FEE_HISTORY`] blocks. Blocks that did not execute any EVM transactions have no entry.
+///
+/// Entries are keyed by `height % FEE_HISTORY`, so the height of a read entry must be checked
+/// against the requested height.
+pub enum BlockGasStore {}
+
+impl Store for BlockGasStore {
+    type K = u64;
+
+    type V = RawBlockGas;
+
+    fn encode_key(height: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 9]>(RawKey(
+            [BLOCK_GAS_PREFIX],
+            (height % FEE_HISTORY).to_be_bytes(),
+        ))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        bytemuck::must_cast_ref::<Self::V, [u8; 64]>(value).to_vec()
     }
 
-    fn decode(bz: &[u8]) -> Self::V {
-        Bytecode::new_raw(bz.to_vec().into())
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<64>(bz).map(bytemuck::must_cast)
     }
 }
 
+/// The receipts of all committed transactions, by transaction hash.
+pub enum ReceiptStore {}
+
+impl Store for ReceiptStore {
+    type K = B256;
+
+    type V = TransactionReceipt;
+
+    fn encode_key(hash: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 33]>(RawKey([RECEIPTS_PREFIX], hash.0))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        to_json_vec(value).expect("receipt is serializable; qed;")
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        from_json(bz)
+    }
+}
+
+/// The blocks that executed an EVM transaction, by height.
+pub enum BlockStore {}
+
+impl Store for BlockStore {
+    type K = u64;
+
+    type V = Block;
+
+    fn encode_key(height: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 9]>(RawKey([BLOCKS_PREFIX], height.to_be_bytes()))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        to_json_vec(value).expect("block is serializable; qed;")
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        from_json(bz)
+    }
+}
+
+/// The height of the last block in [`BlockStore`], the parent of the next recorded block.
+pub enum LatestBlockStore {}
+
+impl Store for LatestBlockStore {
+    type K = ();
+
+    type V = u64;
+
+    fn encode_key((): Self::K) -> impl AsRef<[u8]> {
+        [LATEST_BLOCK_PREFIX]
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        value.to_be_bytes().to_vec()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<8>(bz).map(u64::from_be_bytes)
+    }
+}
+
+/// The heights of the blocks in [`BlockStore`], by block hash.
+pub enum BlockNumberStore {}
+
+impl Store for BlockNumberStore {
+    type K = B256;
+
+    type V = u64;
+
+    fn encode_key(hash: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 33]>(RawKey([BLOCK_NUMBERS_PREFIX], hash.0))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        value.to_be_bytes().to_vec()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<8>(bz).map(u64::from_be_bytes)
+    }
+}
+
+/// The nodes of the state trie and the storage tries, by the keccak256 of their RLP encoding.
+pub enum TrieNodeStore {}
+
+impl Store for TrieNodeStore {
+    type K = B256;
+
+    type V = Vec<u8>;
+
+    fn encode_key(hash: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 33]>(RawKey([TRIE_NODES_PREFIX], hash.0))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        value.clone()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        Ok(bz.to_vec())
+    }
+}
+
+/// The roots of the storage tries of all accounts with storage, if the state trie is enabled.
+pub enum StorageRootStore {}
+
+impl Store for StorageRootStore {
+    type K = Address;
+
+    type V = B256;
+
+    fn encode_key(address: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 21]>(RawKey([STORAGE_ROOTS_PREFIX], address.0.0))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        value.to_vec()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<32>(bz).map(B256::from)
+    }
+}
+
+/// The root of the state trie. Only set if the state trie is enabled.
+pub enum StateRootStore {}
+
+impl Store for StateRootStore {
+    type K = ();
+
+    type V = B256;
+
+    fn encode_key((): Self::K) -> impl AsRef<[u8]> {
+        [STATE_ROOT_PREFIX]
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        value.to_vec()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<32>(bz).map(B256::from)
+    }
+}
+
+/// The EVM contracts awaiting the result of an execution queued with the wasm precompile and the
+/// value they sent with it, refunded if the execution fails, by the id of the execution.
+pub enum WasmReplyStore {}
+
+impl Store for WasmReplyStore {
+    type K = u64;
+
+    type V = (Address, U256);
+
+    fn encode_key(id: Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 9]>(RawKey([WASM_REPLIES_PREFIX], id.to_be_bytes()))
+    }
+
+    fn encode((from, funds): &Self::V) -> Vec<u8> {
+        [from.as_slice(), &funds.to_be_bytes::<32>()].concat()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        let bz = fixed::<52>(bz)?;
+
+        Ok((
+            Address::from_slice(&bz[..20]),
+            U256::from_be_slice(&bz[20..]),
+        ))
+    }
+}
+
+/// All logs, keyed by block height and the index of the log in its block.
+pub enum LogStore {}
+
+impl Store for LogStore {
+    type K = (u64, u64);
+
+    type V = Log;
+
+    fn encode_key((height, log_index): Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 17]>(RawKey([LOGS_PREFIX], log_key(height, log_index)))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        to_json_vec(value).expect("log is serializable; qed;")
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        from_json(bz)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct RawLogId {
+    pub height: u64,
+    pub log_index: u64,
+}
+
+/// An index of [`LogStore`] by the address that emitted the log.
+pub enum LogsByAddressStore {}
+
+impl Store for LogsByAddressStore {
+    type K = (Address, u64, u64);
+
+    type V = RawLogId;
+
+    fn encode_key((address, height, log_index): Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 37]>(RawKey(
+            [LOGS_BY_ADDRESS_PREFIX],
+            bytemuck::must_cast::<_, [u8; 36]>(RawKey(address.0.0, log_key(height, log_index))),
+        ))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        bytemuck::must_cast_ref::<Self::V, [u8; 16]>(value).to_vec()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<16>(bz).map(bytemuck::must_cast)
+    }
+}
+
+/// An index of [`LogStore`] by the first topic of the log.
+pub enum LogsByTopicStore {}
+
+impl Store for LogsByTopicStore {
+    type K = (B256, u64, u64);
+
+    type V = RawLogId;
+
+    fn encode_key((topic, height, log_index): Self::K) -> impl AsRef<[u8]> {
+        bytemuck::must_cast::<_, [u8; 49]>(RawKey(
+            [LOGS_BY_TOPIC_PREFIX],
+            bytemuck::must_cast::<_, [u8; 48]>(RawKey(topic.0, log_key(height, log_index))),
+        ))
+    }
+
+    fn encode(value: &Self::V) -> Vec<u8> {
+        bytemuck::must_cast_ref::<Self::V, [u8; 16]>(value).to_vec()
+    }
+
+    fn decode(bz: &[u8]) -> StdResult<Self::V> {
+        fixed::<16>(bz).map(bytemuck::must_cast)
+    }
+}
+
+/// The big endian height and log index, so that logs are ordered by them in range scans.
+fn log_key(height: u64, log_index: u64) -> [u8; 16] {
+    bytemuck::must_cast(RawKey(height.to_be_bytes(), log_index.to_be_bytes()))
+}
+
 pub trait StorageExt {
-    fn read<T: Store>(&self, k: T::K) -> Option<T::V>;
+    fn read<T: Store>(&self, k: T::K) -> Result<Option<T::V>, StorageError>;
 
     fn write<T: Store>(&mut self, k: T::K, v: &T::V);
+
+    fn delete<T: Store>(&mut self, k: T::K);
+
+    /// The values of all keys from `start` (inclusive) to `end` (exclusive), in ascending order.
+    fn scan<T: Store>(
+        &self,
+        start: T::K,
+        end: T::K,
+    ) -> impl Iterator<Item = Result<T::V, StorageError>>;
 }
 
 impl StorageExt for dyn Storage + '_ {
-    fn read<T: Store>(&self, key: T::K) -> Option<T::V> {
-        self.get(T::encode_key(key).as_ref())
-            .map(|raw| T::decode(&raw))
+    fn read<T: Store>(&self, key: T::K) -> Result<Option<T::V>, StorageError> {
+        let key = T::encode_key(key);
+
+        self.get(key.as_ref())
+            .map(|raw| {
+                T::decode(&raw).map_err(|source| StorageError {
+                    key: key.as_ref().to_vec().into(),
+                    source,
+                })
+            })
+            .transpose()
     }
 
     fn write<T: Store>(&mut self, k: T::K, v: &T::V) {
         self.set(T::encode_key(k).as_ref(), T::encode(v).as_ref());
     }
+
+    fn delete<T: Store>(&mut self, k: T::K) {
+        self.remove(T::encode_key(k).as_ref());
+    }
+
+    fn scan<T: Store>(
+        &self,
+        start: T::K,
+        end: T::K,
+    ) -> impl Iterator<Item = Result<T::V, StorageError>> {
+        self.range(
+            Some(T::encode_key(start).as_ref()),
+            Some(T::encode_key(end).as_ref()),
+            Order::Ascending,
+        )
+        .map(|(key, raw)| {
+            T::decode(&raw).map_err(|source| StorageError {
+                key: key.into(),
+                source,
+            })
+        })
+    }
 }
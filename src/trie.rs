@@ -0,0 +1,666 @@
+//! A Merkle-Patricia trie, as specified in appendix D of the ethereum yellow paper, with its nodes
+//! kept in contract storage by hash.
+//!
+//! Nodes are never deleted, since identical subtries of different tries share their nodes.
+//!
+//! The state trie is optional, see [`InstantiateMsg::state_trie`](crate::msg::InstantiateMsg).
+
+use alloy_rlp::{EMPTY_STRING_CODE, Encodable, Header, PayloadView, RlpEncodable};
+use cosmwasm_std::{StdError, Storage};
+use revm::primitives::{Address, B256, KECCAK_EMPTY, U256, b256, keccak256};
+
+use crate::{
+    error::StorageError,
+    state::{RawAccountInfo, StateRootStore, StorageExt, StorageRootStore, Store, TrieNodeStore},
+};
+
+/// The root of an empty trie, the keccak256 of the RLP encoding of an empty string.
+pub const EMPTY_ROOT: B256 =
+    b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+enum Node {
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: NodeRef,
+    },
+    Branch {
+        children: Box<[NodeRef; 16]>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+/// A reference to a node from its parent. Nodes with an encoding shorter than 32 bytes are embedded
+/// in their parent rather than referenced by hash.
+enum NodeRef {
+    Empty,
+    Hash(B256),
+    Inline(Box<Node>),
+}
+
+/// An account as stored in the state trie.
+#[derive(RlpEncodable)]
+pub struct TrieAccount {
+    pub nonce: u64,
+    pub balance: U256,
+    pub storage_root: B256,
+    pub code_hash: B256,
+}
+
+/// Updates the state trie, if it is enabled, after the account at `address` was set to `info` and
+/// the storage slots in `slots` were set to the given values.
+pub fn update_account(
+    storage: &mut dyn Storage,
+    address: Address,
+    info: &RawAccountInfo,
+    slots: impl IntoIterator<Item = (U256, U256)>,
+) -> Result<(), StorageError> {
+    let Some(state_root) = storage.read::<StateRootStore>(())? else {
+        return Ok(());
+    };
+
+    let mut storage_root = storage
+        .read::<StorageRootStore>(address)?
+        .unwrap_or(EMPTY_ROOT);
+    let mut slots = slots.into_iter().peekable();
+
+    if slots.peek().is_some() {
+        for (slot, value) in slots {
+            // zero slots are not part of the trie, an empty value removes them
+            let value = if value.is_zero() {
+                vec![]
+            } else {
+                alloy_rlp::encode(value)
+            };
+
+            storage_root = insert(
+                storage,
+                storage_root,
+                keccak256(slot.to_be_bytes::<32>()).as_slice(),
+                value,
+            )?;
+        }
+
+        storage.write::<StorageRootStore>(address, &storage_root);
+    }
+
+    let account = TrieAccount {
+        nonce: info.nonce,
+        balance: U256::from_be_bytes(info.balance),
+        storage_root,
+        code_hash: info.code_hash.into(),
+    };
+
+    // empty accounts are not part of the trie, as specified by EIP-161
+    let value = if account.nonce == 0
+        && account.balance.is_zero()
+        && account.storage_root == EMPTY_ROOT
+        && account.code_hash == KECCAK_EMPTY
+    {
+        vec![]
+    } else {
+        alloy_rlp::encode(&account)
+    };
+
+    let state_root = insert(storage, state_root, keccak256(address).as_slice(), value)?;
+    storage.write::<StateRootStore>((), &state_root);
+
+    Ok(())
+}
+
+/// The value at `key` in the trie with `root`.
+pub fn get(storage: &dyn Storage, root: B256, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+    walk(storage, root, key, |_| {})
+}
+
+/// The proof of the value at `key` in the trie with `root`, or of its absence, in the manner of
+/// `eth_getProof`: the encodings of all nodes referenced by hash on the path to `key`, starting with
+/// the root.
+pub fn proof(storage: &dyn Storage, root: B256, key: &[u8]) -> Result<Vec<Vec<u8>>, StorageError> {
+    let mut proof = vec![];
+    walk(storage, root, key, |encoded| proof.push(encoded.to_vec()))?;
+    Ok(proof)
+}
+
+/// Looks up `key` in the trie with `root`, calling `visit` with the encoding of every node
+/// referenced by hash on the way.
+fn walk(
+    storage: &dyn Storage,
+    root: B256,
+    key: &[u8],
+    mut visit: impl FnMut(&[u8]),
+) -> Result<Option<Vec<u8>>, StorageError> {
+    let mut node = if root == EMPTY_ROOT {
+        NodeRef::Empty
+    } else {
+        NodeRef::Hash(root)
+    };
+    let path = nibbles(key);
+    let mut path = path.as_slice();
+
+    loop {
+        let current = match node {
+            NodeRef::Empty => return Ok(None),
+            NodeRef::Hash(hash) => {
+                let (encoded, node) = read(storage, hash)?;
+                visit(&encoded);
+                node
+            }
+            NodeRef::Inline(node) => *node,
+        };
+
+        match current {
+            Node::Leaf { path: leaf, value } => {
+                return Ok((leaf == path).then_some(value));
+            }
+            Node::Extension {
+                path: extension,
+                child,
+            } => {
+                let Some(rest) = path.strip_prefix(extension.as_slice()) else {
+                    return Ok(None);
+                };
+                path = rest;
+                node = child;
+            }
+            Node::Branch {
+                mut children,
+                value,
+            } => {
+                let Some((index, rest)) = path.split_first() else {
+                    return Ok(value);
+                };
+                path = rest;
+                node = std::mem::replace(&mut children[*index as usize], NodeRef::Empty);
+            }
+        }
+    }
+}
+
+/// Sets `key` to `value` in the trie with `root`, returning the new root. An empty value removes the
+/// key, as on ethereum.
+pub fn insert(
+    storage: &mut dyn Storage,
+    root: B256,
+    key: &[u8],
+    value: Vec<u8>,
+) -> Result<B256, StorageError> {
+    if value.is_empty() {
+        return remove(storage, root, key);
+    }
+
+    let node = load_root(storage, root)?;
+    let node = insert_at(storage, node, &nibbles(key), value)?;
+
+    Ok(store_root(storage, &node))
+}
+
+/// Removes `key` from the trie with `root`, returning the new root.
+pub fn remove(storage: &mut dyn Storage, root: B256, key: &[u8]) -> Result<B256, StorageError> {
+    let Some(node) = load_root(storage, root)? else {
+        return Ok(EMPTY_ROOT);
+    };
+
+    Ok(match remove_at(storage, node, &nibbles(key))? {
+        Some(node) => store_root(storage, &node),
+        None => EMPTY_ROOT,
+    })
+}
+
+fn insert_at(
+    storage: &mut dyn Storage,
+    node: Option<Node>,
+    path: &[u8],
+    value: Vec<u8>,
+) -> Result<Node, StorageError> {
+    let Some(node) = node else {
+        return Ok(Node::Leaf {
+            path: path.to_vec(),
+            value,
+        });
+    };
+
+    Ok(match node {
+        Node::Leaf {
+            path: leaf,
+            value: leaf_value,
+        } => {
+            let common = common_prefix(&leaf, path);
+
+            if common == leaf.len() && common == path.len() {
+                return Ok(Node::Leaf { path: leaf, value });
+            }
+
+            let mut branch = empty_branch();
+            branch_insert_leaf(storage, &mut branch, &leaf[common..], leaf_value);
+            branch_insert_leaf(storage, &mut branch, &path[common..], value);
+
+            with_extension(storage, &path[..common], branch)
+        }
+        Node::Extension {
+            path: extension,
+            child,
+        } => {
+            let common = common_prefix(&extension, path);
+
+            if common == extension.len() {
+                let child = load(storage, child)?;
+                let child = insert_at(storage, child, &path[common..], value)?;
+
+                return Ok(Node::Extension {
+                    path: extension,
+                    child: store(storage, child),
+                });
+            }
+
+            let mut branch = empty_branch();
+            if let Node::Branch { children, .. } = &mut branch {
+                children[extension[common] as usize] = if extension.len() == common + 1 {
+                    child
+                } else {
+                    store(
+                        storage,
+                        Node::Extension {
+                            path: extension[common + 1..].to_vec(),
+                            child,
+                        },
+                    )
+                };
+            }
+            branch_insert_leaf(storage, &mut branch, &path[common..], value);
+
+            with_extension(storage, &path[..common], branch)
+        }
+        Node::Branch {
+            mut children,
+            value: branch_value,
+        } => match path.split_first() {
+            None => Node::Branch {
+                children,
+                value: Some(value),
+            },
+            Some((index, rest)) => {
+                let child = std::mem::replace(&mut children[*index as usize], NodeRef::Empty);
+                let child = load(storage, child)?;
+                let child = insert_at(storage, child, rest, value)?;
+                children[*index as usize] = store(storage, child);
+
+                Node::Branch {
+                    children,
+                    value: branch_value,
+                }
+            }
+        },
+    })
+}
+
+fn remove_at(
+    storage: &mut dyn Storage,
+    node: Node,
+    path: &[u8],
+) -> Result<Option<Node>, StorageError> {
+    Ok(match node {
+        Node::Leaf { path: leaf, value } => {
+            (leaf != path).then_some(Node::Leaf { path: leaf, value })
+        }
+        Node::Extension {
+            path: extension,
+            child,
+        } => {
+            let Some(rest) = path.strip_prefix(extension.as_slice()) else {
+                return Ok(Some(Node::Extension {
+                    path: extension,
+                    child,
+                }));
+            };
+
+            let Some(child) = load(storage, child)? else {
+                return Ok(None);
+            };
+
+            remove_at(storage, child, rest)?.map(|child| join(storage, extension, child))
+        }
+        Node::Branch {
+            mut children,
+            mut value,
+        } => {
+            match path.split_first() {
+                None => value = None,
+                Some((index, rest)) => {
+                    let child = std::mem::replace(&mut children[*index as usize], NodeRef::Empty);
+                    if let Some(child) = load(storage, child)? {
+                        children[*index as usize] = match remove_at(storage, child, rest)? {
+                            Some(child) => store(storage, child),
+                            None => NodeRef::Empty,
+                        };
+                    }
+                }
+            }
+
+            let remaining = (0..16_u8)
+                .filter(|index| !matches!(children[*index as usize], NodeRef::Empty))
+                .collect::<Vec<_>>();
+
+            // a branch with a single child or only a value is collapsed into it
+            match (remaining.as_slice(), value) {
+                ([], None) => None,
+                ([], Some(value)) => Some(Node::Leaf {
+                    path: vec![],
+                    value,
+                }),
+                ([index], None) => {
+                    let child = std::mem::replace(&mut children[*index as usize], NodeRef::Empty);
+
+                    load(storage, child)?.map(|child| join(storage, vec![*index], child))
+                }
+                (_, value) => Some(Node::Branch { children, value }),
+            }
+        }
+    })
+}
+
+/// The node at `path` followed by `child`.
+fn join(storage: &mut dyn Storage, mut path: Vec<u8>, child: Node) -> Node {
+    match child {
+        Node::Leaf { path: leaf, value } => {
+            path.extend(leaf);
+            Node::Leaf { path, value }
+        }
+        Node::Extension {
+            path: extension,
+            child,
+        } => {
+            path.extend(extension);
+            Node::Extension { path, child }
+        }
+        branch @ Node::Branch { .. } => Node::Extension {
+            path,
+            child: store(storage, branch),
+        },
+    }
+}
+
+fn empty_branch() -> Node {
+    Node::Branch {
+        children: Box::new(std::array::from_fn(|_| NodeRef::Empty)),
+        value: None,
+    }
+}
+
+/// Adds a leaf for the remaining `path` to `branch`.
+fn branch_insert_leaf(storage: &mut dyn Storage, branch: &mut Node, path: &[u8], value: Vec<u8>) {
+    if let Node::Branch {
+        children,
+        value: branch_value,
+    } = branch
+    {
+        match path.split_first() {
+            None => *branch_value = Some(value),
+            Some((index, rest)) => {
+                children[*index as usize] = store(
+                    storage,
+                    Node::Leaf {
+                        path: rest.to_vec(),
+                        value,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// `branch`, preceded by an extension for `path` if it is not empty.
+fn with_extension(storage: &mut dyn Storage, path: &[u8], branch: Node) -> Node {
+    if path.is_empty() {
+        branch
+    } else {
+        Node::Extension {
+            path: path.to_vec(),
+            child: store(storage, branch),
+        }
+    }
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+fn nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter()
+        .flat_map(|byte| [byte >> 4, byte & 0xf])
+        .collect()
+}
+
+fn load_root(storage: &dyn Storage, root: B256) -> Result<Option<Node>, StorageError> {
+    if root == EMPTY_ROOT {
+        Ok(None)
+    } else {
+        load(storage, NodeRef::Hash(root))
+    }
+}
+
+fn load(storage: &dyn Storage, node: NodeRef) -> Result<Option<Node>, StorageError> {
+    match node {
+        NodeRef::Empty => Ok(None),
+        NodeRef::Hash(hash) => read(storage, hash).map(|(_, node)| Some(node)),
+        NodeRef::Inline(node) => Ok(Some(*node)),
+    }
+}
+
+/// The encoding of the node with `hash`, and the node itself.
+fn read(storage: &dyn Storage, hash: B256) -> Result<(Vec<u8>, Node), StorageError> {
+    let error = |source| StorageError {
+        key: TrieNodeStore::encode_key(hash).as_ref().to_vec().into(),
+        source,
+    };
+
+    let encoded = storage
+        .read::<TrieNodeStore>(hash)?
+        .ok_or_else(|| error(StdError::not_found("trie node")))?;
+
+    let node = decode(&encoded).map_err(|err| error(StdError::parse_err("trie node", err)))?;
+
+    Ok((encoded, node))
+}
+
+/// Stores `node` if it is not embedded in its parent and returns the reference to it.
+fn store(storage: &mut dyn Storage, node: Node) -> NodeRef {
+    let encoded = encode(&node);
+
+    if encoded.len() < 32 {
+        NodeRef::Inline(Box::new(node))
+    } else {
+        let hash = keccak256(&encoded);
+        storage.write::<TrieNodeStore>(hash, &encoded);
+        NodeRef::Hash(hash)
+    }
+}
+
+/// Stores the root `node`, which is always referenced by hash, and returns its hash.
+fn store_root(storage: &mut dyn Storage, node: &Node) -> B256 {
+    let encoded = encode(node);
+    let hash = keccak256(&encoded);
+    storage.write::<TrieNodeStore>(hash, &encoded);
+    hash
+}
+
+/// The hex prefix encoding of a path, as specified in appendix C of the yellow paper.
+fn encode_path(path: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 0x20 } else { 0 };
+    let (first, rest) = if path.len() % 2 == 1 {
+        (flag | 0x10 | path[0], &path[1..])
+    } else {
+        (flag, path)
+    };
+
+    std::iter::once(first)
+        .chain(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]))
+        .collect()
+}
+
+fn decode_path(encoded: &[u8]) -> Result<(Vec<u8>, bool), alloy_rlp::Error> {
+    let (first, rest) = encoded
+        .split_first()
+        .ok_or(alloy_rlp::Error::Custom("empty path"))?;
+
+    let mut path = if first & 0x10 == 0 {
+        vec![]
+    } else {
+        vec![first & 0xf]
+    };
+    path.extend(nibbles(rest));
+
+    Ok((path, first & 0x20 != 0))
+}
+
+fn encode(node: &Node) -> Vec<u8> {
+    let mut payload = vec![];
+
+    match node {
+        Node::Leaf { path, value } => {
+            encode_path(path, true).as_slice().encode(&mut payload);
+            value.as_slice().encode(&mut payload);
+        }
+        Node::Extension { path, child } => {
+            encode_path(path, false).as_slice().encode(&mut payload);
+            encode_ref(child, &mut payload);
+        }
+        Node::Branch { children, value } => {
+            for child in children.iter() {
+                encode_ref(child, &mut payload);
+            }
+            value.as_deref().unwrap_or_default().encode(&mut payload);
+        }
+    }
+
+    let mut out = vec![];
+    Header {
+        list: true,
+        payload_length: payload.len(),
+    }
+    .encode(&mut out);
+    out.extend(payload);
+    out
+}
+
+fn encode_ref(node: &NodeRef, out: &mut Vec<u8>) {
+    match node {
+        NodeRef::Empty => out.push(EMPTY_STRING_CODE),
+        NodeRef::Hash(hash) => hash.as_slice().encode(out),
+        NodeRef::Inline(node) => out.extend(encode(node)),
+    }
+}
+
+fn decode(mut encoded: &[u8]) -> Result<Node, alloy_rlp::Error> {
+    let PayloadView::List(items) = Header::decode_raw(&mut encoded)? else {
+        return Err(alloy_rlp::Error::UnexpectedString);
+    };
+
+    match items.as_slice() {
+        [path, value] => {
+            let (path, leaf) = decode_path(Header::decode_bytes(&mut &path[..], false)?)?;
+
+            Ok(if leaf {
+                Node::Leaf {
+                    path,
+                    value: Header::decode_bytes(&mut &value[..], false)?.to_vec(),
+                }
+            } else {
+                Node::Extension {
+                    path,
+                    child: decode_ref(value)?,
+                }
+            })
+        }
+        [children @ .., value] if children.len() == 16 => {
+            let mut refs = Vec::with_capacity(16);
+            for child in children {
+                refs.push(decode_ref(child)?);
+            }
+
+            let value = Header::decode_bytes(&mut &value[..], false)?;
+
+            Ok(Node::Branch {
+                children: Box::new(
+                    refs.try_into()
+                        .map_err(|_| alloy_rlp::Error::Custom("invalid branch"))?,
+                ),
+                value: (!value.is_empty()).then(|| value.to_vec()),
+            })
+        }
+        _ => Err(alloy_rlp::Error::Custom("invalid trie node")),
+    }
+}
+
+fn decode_ref(encoded: &[u8]) -> Result<NodeRef, alloy_rlp::Error> {
+    if encoded
+        .first()
+        .is_some_and(|byte| *byte >= alloy_rlp::EMPTY_LIST_CODE)
+    {
+        return decode(encoded).map(|node| NodeRef::Inline(Box::new(node)));
+    }
+
+    match Header::decode_bytes(&mut &encoded[..], false)? {
+        [] => Ok(NodeRef::Empty),
+        hash => B256::try_from(hash)
+            .map(NodeRef::Hash)
+            .map_err(|_| alloy_rlp::Error::Custom("invalid node reference")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::MockStorage;
+
+    use super::*;
+
+    /// The test vectors of `TestInsert` and `TestDelete` in go-ethereum.
+    #[test]
+    fn geth_vectors() {
+        let mut storage = MockStorage::new();
+
+        let mut root = EMPTY_ROOT;
+        for (key, value) in [
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ] {
+            root = insert(&mut storage, root, key.as_bytes(), value.into()).unwrap();
+        }
+        assert_eq!(
+            root,
+            b256!("8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3")
+        );
+        assert_eq!(
+            get(&storage, root, b"dog").unwrap(),
+            Some(b"puppy".to_vec())
+        );
+        assert_eq!(get(&storage, root, b"do").unwrap(), None);
+
+        let mut root = EMPTY_ROOT;
+        for (key, value) in [
+            ("do", "verb"),
+            ("ether", "wookiedoo"),
+            ("horse", "stallion"),
+            ("shaman", "horse"),
+            ("doge", "coin"),
+            ("ether", ""),
+            ("dog", "puppy"),
+            ("shaman", ""),
+        ] {
+            root = insert(&mut storage, root, key.as_bytes(), value.into()).unwrap();
+        }
+        assert_eq!(
+            root,
+            b256!("5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84")
+        );
+
+        for key in ["do", "horse", "doge", "dog"] {
+            root = remove(&mut storage, root, key.as_bytes()).unwrap();
+        }
+        assert_eq!(root, EMPTY_ROOT);
+    }
+}
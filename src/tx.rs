@@ -0,0 +1,356 @@
+use alloy_rlp::{Decodable, Encodable, Header, RlpDecodable, RlpEncodable};
+use cosmwasm_std::Api;
+use revm::primitives::{AccessListItem, Address, B256, Bytes, TxEnv, TxKind, U256, hex, keccak256};
+
+use crate::error::ContractError;
+
+const EIP2930_TX_TYPE: u8 = 0x01;
+const EIP1559_TX_TYPE: u8 = 0x02;
+
+/// Half of the order of the secp256k1 curve. Signatures with an `s` value above this are rejected,
+/// as per [EIP-2](https://eips.ethereum.org/EIPS/eip-2).
+const SECP256K1N_HALF: U256 = U256::from_be_bytes(hex!(
+    "7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a0"
+));
+
+/// The hash of an unsigned transaction, as executed with [`ExecuteMsg::Transaction`].
+///
+/// This is the keccak256 of the RLP encoding of its chain ID, sender, nonce, gas price, priority
+/// fee, gas limit, destination, value and input. The sender and nonce make it unique among
+/// committed transactions.
+///
+/// [`ExecuteMsg::Transaction`]: crate::msg::ExecuteMsg::Transaction
+#[must_use]
+pub fn unsigned_tx_hash(chain_id: u64, tx: &TxEnv) -> B256 {
+    let nonce = tx.nonce.unwrap_or_default();
+    let gas_priority_fee = tx.gas_priority_fee.unwrap_or_default();
+
+    let fields: [&dyn Encodable; 9] = [
+        &chain_id,
+        &tx.caller,
+        &nonce,
+        &tx.gas_price,
+        &gas_priority_fee,
+        &tx.gas_limit,
+        &tx.transact_to,
+        &tx.value,
+        &tx.data,
+    ];
+
+    let mut out = vec![];
+    alloy_rlp::encode_list::<_, dyn Encodable>(&fields, &mut out);
+
+    keccak256(out)
+}
+
+/// A signed ethereum transaction, as sent to `eth_sendRawTransaction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTx {
+    pub tx: TypedTx,
+    pub signature: Signature,
+    /// The hash of the transaction, i.e. the keccak256 of its encoding.
+    pub hash: B256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypedTx {
+    Legacy(TxLegacy),
+    Eip2930(TxEip2930),
+    Eip1559(TxEip1559),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    pub r: U256,
+    pub s: U256,
+    pub y_parity: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxLegacy {
+    /// Not set for transactions signed before [EIP-155](https://eips.ethereum.org/EIPS/eip-155).
+    pub chain_id: Option<u64>,
+    pub nonce: u64,
+    pub gas_price: u128,
+    pub gas_limit: u64,
+    pub to: TxKind,
+    pub value: U256,
+    pub input: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxEip2930 {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub gas_price: u128,
+    pub gas_limit: u64,
+    pub to: TxKind,
+    pub value: U256,
+    pub input: Bytes,
+    pub access_list: Vec<AccessListEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxEip1559 {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    pub to: TxKind,
+    pub value: U256,
+    pub input: Bytes,
+    pub access_list: Vec<AccessListEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, RlpEncodable, RlpDecodable)]
+pub struct AccessListEntry {
+    pub address: Address,
+    pub storage_keys: Vec<B256>,
+}
+
+impl SignedTx {
+    /// Decodes a legacy or [EIP-2718](https://eips.ethereum.org/EIPS/eip-2718) typed transaction.
+    pub fn decode(raw: &[u8]) -> Result<Self, ContractError> {
+        Self::decode_inner(raw).map_err(ContractError::InvalidRawTransaction)
+    }
+
+    fn decode_inner(raw: &[u8]) -> alloy_rlp::Result<Self> {
+        let (ty, mut buf) = match raw.first() {
+            Some(&ty @ (EIP2930_TX_TYPE | EIP1559_TX_TYPE)) => (Some(ty), &raw[1..]),
+            Some(0xc0..) => (None, raw),
+            Some(_) => return Err(alloy_rlp::Error::Custom("unsupported transaction type")),
+            None => return Err(alloy_rlp::Error::InputTooShort),
+        };
+
+        let header = Header::decode(&mut buf)?;
+
+        if !header.list {
+            return Err(alloy_rlp::Error::UnexpectedString);
+        }
+
+        if header.payload_length != buf.len() {
+            return Err(alloy_rlp::Error::UnexpectedLength);
+        }
+
+        let buf = &mut buf;
+
+        let (tx, signature) = match ty {
+            None => {
+                let nonce = Decodable::decode(buf)?;
+                let gas_price = Decodable::decode(buf)?;
+                let gas_limit = Decodable::decode(buf)?;
+                let to = Decodable::decode(buf)?;
+                let value = Decodable::decode(buf)?;
+                let input = Decodable::decode(buf)?;
+                let v = u64::decode(buf)?;
+                let r = Decodable::decode(buf)?;
+                let s = Decodable::decode(buf)?;
+
+                // see https://eips.ethereum.org/EIPS/eip-155
+                let (chain_id, y_parity) = match v {
+                    27 | 28 => (None, v == 28),
+                    35.. => (Some((v - 35) / 2), (v - 35) % 2 == 1),
+                    _ => return Err(alloy_rlp::Error::Custom("invalid v value")),
+                };
+
+                (
+                    TypedTx::Legacy(TxLegacy {
+                        chain_id,
+                        nonce,
+                        gas_price,
+                        gas_limit,
+                        to,
+                        value,
+                        input,
+                    }),
+                    Signature { r, s, y_parity },
+                )
+            }
+            Some(EIP2930_TX_TYPE) => (
+                TypedTx::Eip2930(TxEip2930 {
+                    chain_id: Decodable::decode(buf)?,
+                    nonce: Decodable::decode(buf)?,
+                    gas_price: Decodable::decode(buf)?,
+                    gas_limit: Decodable::decode(buf)?,
+                    to: Decodable::decode(buf)?,
+                    value: Decodable::decode(buf)?,
+                    input: Decodable::decode(buf)?,
+                    access_list: Decodable::decode(buf)?,
+                }),
+                Signature {
+                    y_parity: Decodable::decode(buf)?,
+                    r: Decodable::decode(buf)?,
+                    s: Decodable::decode(buf)?,
+                },
+            ),
+            Some(_) => (
+                TypedTx::Eip1559(TxEip1559 {
+                    chain_id: Decodable::decode(buf)?,
+                    nonce: Decodable::decode(buf)?,
+                    max_priority_fee_per_gas: Decodable::decode(buf)?,
+                    max_fee_per_gas: Decodable::decode(buf)?,
+                    gas_limit: Decodable::decode(buf)?,
+                    to: Decodable::decode(buf)?,
+                    value: Decodable::decode(buf)?,
+                    input: Decodable::decode(buf)?,
+                    access_list: Decodable::decode(buf)?,
+                }),
+                Signature {
+                    y_parity: Decodable::decode(buf)?,
+                    r: Decodable::decode(buf)?,
+                    s: Decodable::decode(buf)?,
+                },
+            ),
+        };
+
+        if !buf.is_empty() {
+            return Err(alloy_rlp::Error::ListLengthMismatch {
+                expected: header.payload_length,
+                got: header.payload_length + buf.len(),
+            });
+        }
+
+        Ok(Self {
+            tx,
+            signature,
+            hash: keccak256(raw),
+        })
+    }
+
+    /// Recovers the address that signed this transaction.
+    pub fn recover_signer(&self, api: &dyn Api) -> Result<Address, ContractError> {
+        if self.signature.s > SECP256K1N_HALF {
+            return Err(ContractError::InvalidSignature(
+                "s value is too high".to_owned(),
+            ));
+        }
+
+        let signature = [
+            self.signature.r.to_be_bytes::<32>(),
+            self.signature.s.to_be_bytes::<32>(),
+        ]
+        .concat();
+
+        let pubkey = api
+            .secp256k1_recover_pubkey(
+                self.tx.signing_hash().as_slice(),
+                &signature,
+                self.signature.y_parity.into(),
+            )
+            .map_err(|err| ContractError::InvalidSignature(err.to_string()))?;
+
+        // the recovered pubkey is uncompressed, prefixed with 0x04
+        Ok(Address::from_raw_public_key(&pubkey[1..]))
+    }
+}
+
+impl TypedTx {
+    /// The hash that is signed by the sender of the transaction.
+    #[must_use]
+    pub fn signing_hash(&self) -> B256 {
+        let mut out = vec![];
+
+        match self {
+            Self::Legacy(tx) => {
+                let mut fields: Vec<&dyn Encodable> = vec![
+                    &tx.nonce,
+                    &tx.gas_price,
+                    &tx.gas_limit,
+                    &tx.to,
+                    &tx.value,
+                    &tx.input,
+                ];
+
+                if let Some(chain_id) = &tx.chain_id {
+                    fields.extend::<[&dyn Encodable; 3]>([chain_id, &0_u8, &0_u8]);
+                }
+
+                alloy_rlp::encode_list::<_, dyn Encodable>(&fields, &mut out);
+            }
+            Self::Eip2930(tx) => {
+                let fields: [&dyn Encodable; 8] = [
+                    &tx.chain_id,
+                    &tx.nonce,
+                    &tx.gas_price,
+                    &tx.gas_limit,
+                    &tx.to,
+                    &tx.value,
+                    &tx.input,
+                    &tx.access_list,
+                ];
+
+                out.push(EIP2930_TX_TYPE);
+                alloy_rlp::encode_list::<_, dyn Encodable>(&fields, &mut out);
+            }
+            Self::Eip1559(tx) => {
+                let fields: [&dyn Encodable; 9] = [
+                    &tx.chain_id,
+                    &tx.nonce,
+                    &tx.max_priority_fee_per_gas,
+                    &tx.max_fee_per_gas,
+                    &tx.gas_limit,
+                    &tx.to,
+                    &tx.value,
+                    &tx.input,
+                    &tx.access_list,
+                ];
+
+                out.push(EIP1559_TX_TYPE);
+                alloy_rlp::encode_list::<_, dyn Encodable>(&fields, &mut out);
+            }
+        }
+
+        keccak256(out)
+    }
+
+    /// Fills `tx_env` with the fields of this transaction, sent by `caller`.
+    pub fn fill_tx_env(self, tx_env: &mut TxEnv, caller: Address) {
+        tx_env.caller = caller;
+
+        match self {
+            Self::Legacy(tx) => {
+                tx_env.chain_id = tx.chain_id;
+                tx_env.nonce = Some(tx.nonce);
+                tx_env.gas_limit = tx.gas_limit;
+                tx_env.gas_price = U256::from(tx.gas_price);
+                tx_env.gas_priority_fee = None;
+                tx_env.transact_to = tx.to;
+                tx_env.value = tx.value;
+                tx_env.data = tx.input;
+                tx_env.access_list = vec![];
+            }
+            Self::Eip2930(tx) => {
+                tx_env.chain_id = Some(tx.chain_id);
+                tx_env.nonce = Some(tx.nonce);
+                tx_env.gas_limit = tx.gas_limit;
+                tx_env.gas_price = U256::from(tx.gas_price);
+                tx_env.gas_priority_fee = None;
+                tx_env.transact_to = tx.to;
+                tx_env.value = tx.value;
+                tx_env.data = tx.input;
+                tx_env.access_list = tx.access_list.into_iter().map(Into::into).collect();
+            }
+            Self::Eip1559(tx) => {
+                tx_env.chain_id = Some(tx.chain_id);
+                tx_env.nonce = Some(tx.nonce);
+                tx_env.gas_limit = tx.gas_limit;
+                tx_env.gas_price = U256::from(tx.max_fee_per_gas);
+                tx_env.gas_priority_fee = Some(U256::from(tx.max_priority_fee_per_gas));
+                tx_env.transact_to = tx.to;
+                tx_env.value = tx.value;
+                tx_env.data = tx.input;
+                tx_env.access_list = tx.access_list.into_iter().map(Into::into).collect();
+            }
+        }
+    }
+}
+
+impl From<AccessListEntry> for AccessListItem {
+    fn from(value: AccessListEntry) -> Self {
+        Self {
+            address: value.address,
+            storage_keys: value.storage_keys,
+        }
+    }
+}